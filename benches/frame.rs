@@ -0,0 +1,97 @@
+//! Per-frame cost of preparing a bar with a few hundred nodes for drawing, which is everything
+//! done on the CPU before the render pass is recorded.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use status_bar::{buffers, rectangle, renderer, tree};
+
+const NODES: usize = 300;
+
+struct Gpu {
+    renderer: renderer::Renderer,
+    instance_buffer: buffers::InstanceBuffer,
+}
+
+impl Gpu {
+    fn new() -> Option<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+        let (device, queue) = renderer::request_device(&adapter).ok()?;
+
+        Some(Self {
+            instance_buffer: buffers::InstanceBuffer::new(&device, 256),
+            renderer: renderer::Renderer::new(device, queue),
+        })
+    }
+
+    fn prepare(&mut self, tree: &mut tree::Tree) -> bool {
+        let changed = self.renderer.prepare(tree, &mut self.instance_buffer, 1.0);
+
+        // Flushes the buffer writes, like drawing the frame would
+        self.renderer.queue.submit(None);
+
+        changed
+    }
+}
+
+fn node(index: usize) -> rectangle::Rectangle {
+    rectangle::Rectangle::default()
+        .set_size(20.0, 20.0)
+        .set_padding(2.0, 2.0, 2.0, 2.0)
+        .set_background_color(index as f32 / NODES as f32, 0.5, 0.5, 1.0)
+        .set_border_size(1.0, 1.0, 1.0, 1.0)
+        .set_border_color(1.0, 1.0, 1.0, 1.0)
+        .set_border_radius(4.0, 4.0, 4.0, 4.0)
+}
+
+fn bar() -> tree::Tree {
+    let mut tree = tree::Tree::new(rectangle::Rectangle::default().set_size(8000.0, 30.0));
+
+    for index in 0..NODES {
+        let section = match index % 3 {
+            0 => tree::Section::Start,
+            1 => tree::Section::Center,
+            _ => tree::Section::End,
+        };
+        tree.section_mut(section).add_child(node(index));
+    }
+
+    tree.layout();
+    tree
+}
+
+fn frame(c: &mut Criterion) {
+    let Some(mut gpu) = Gpu::new() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let mut tree = bar();
+    gpu.prepare(&mut tree);
+
+    c.bench_function("unchanged", |b| {
+        b.iter(|| gpu.prepare(&mut tree));
+    });
+
+    // Alternates between two colors so every frame really changes a node
+    let mut flip = false;
+    c.bench_function("one node changed", |b| {
+        b.iter(|| {
+            flip = !flip;
+            let node = &mut tree.section_mut(tree::Section::Center).children[0];
+            let data = std::mem::take(&mut node.data);
+            node.data = data.set_background_color(flip as u32 as f32, 0.0, 0.0, 1.0);
+            gpu.prepare(&mut tree)
+        });
+    });
+
+    c.bench_function("tree rebuilt", |b| {
+        b.iter_batched(
+            bar,
+            |mut tree| gpu.prepare(&mut tree),
+            BatchSize::SmallInput,
+        );
+    });
+}
+
+criterion_group!(benches, frame);
+criterion_main!(benches);
@@ -1,3 +1,5 @@
 fn main() {
-    println!("cargo:rustc-link-lib=vulkan");
+    if std::env::var_os("CARGO_FEATURE_VULKAN").is_some() {
+        println!("cargo:rustc-link-lib=vulkan");
+    }
 }
@@ -1,5 +1,5 @@
 { pkgs, lib, rustPlatform, cargo, rustc, rust-analyzer-unwrapped, pkg-config
-, wayland, vulkan-loader }:
+, wayland, vulkan-loader, fontconfig, libxkbcommon }:
 let cargoToml = builtins.fromTOML (builtins.readFile ./Cargo.toml);
 in rustPlatform.buildRustPackage {
   pname = "status-bar";
@@ -9,7 +9,7 @@ in rustPlatform.buildRustPackage {
     root = ./.;
     fileset = lib.fileset.intersection
       (lib.fileset.fromSource (lib.sources.cleanSource ./.))
-      (lib.fileset.unions [ ./src ./Cargo.toml ./Cargo.lock ]);
+      (lib.fileset.unions [ ./src ./benches ./Cargo.toml ./Cargo.lock ]);
   };
 
   strictDeps = true;
@@ -17,10 +17,10 @@ in rustPlatform.buildRustPackage {
   buildInputs = [ ];
 
   nativeBuildInputs =
-    [ cargo rustc rust-analyzer-unwrapped pkg-config wayland vulkan-loader ];
+    [ cargo rustc rust-analyzer-unwrapped pkg-config wayland vulkan-loader fontconfig libxkbcommon ];
 
   configurePhase = ''
-    export PKG_CONFIG_PATH=${pkgs.wayland.dev}/lib/pkgconfig
+    export PKG_CONFIG_PATH=${pkgs.wayland.dev}/lib/pkgconfig:${pkgs.fontconfig.dev}/lib/pkgconfig
   '';
 
   doCheck = false;
@@ -29,6 +29,7 @@
             cargo
             rustc
             wayland
+            fontconfig
             rust-analyzer-unwrapped
             rustfmt
             clippy
@@ -0,0 +1,180 @@
+/// Texture that many small images are packed into, so they can all be sampled from a single
+/// binding. Regions are allocated in shelves, rows as tall as the tallest region in them.
+pub struct Atlas {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    format: wgpu::TextureFormat,
+    size: u32,
+    // What the device allows `grow` to reach
+    max_size: u32,
+    cursor: [u32; 2],
+    row_height: u32,
+}
+
+/// Region of the atlas, in pixels
+#[derive(Clone, Copy)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Empty space left around each region so linear filtering doesn't bleed between neighbours
+const PADDING: u32 = 1;
+
+impl Atlas {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, size: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Atlas"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            view,
+            format,
+            size,
+            max_size: device.limits().max_texture_dimension_2d,
+            cursor: [PADDING, PADDING],
+            row_height: 0,
+        }
+    }
+
+    // Whether a region this big could be allocated in an empty atlas of the current size
+    fn fits(&self, width: u32, height: u32) -> bool {
+        width + PADDING * 2 <= self.size && height + PADDING * 2 <= self.size
+    }
+
+    /// Whether a region this big could be allocated once the atlas has grown as far as it can
+    pub fn could_fit(&self, width: u32, height: u32) -> bool {
+        width + PADDING * 2 <= self.max_size && height + PADDING * 2 <= self.max_size
+    }
+
+    /// Returns `None` when the atlas is full, callers are expected to `grow` or `clear` it and
+    /// start over
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<Region> {
+        if !self.fits(width, height) {
+            return None;
+        }
+
+        if self.cursor[0] + width + PADDING > self.size {
+            self.cursor = [PADDING, self.cursor[1] + self.row_height + PADDING];
+            self.row_height = 0;
+        }
+
+        if self.cursor[1] + height + PADDING > self.size {
+            return None;
+        }
+
+        let region = Region {
+            x: self.cursor[0],
+            y: self.cursor[1],
+            width,
+            height,
+        };
+
+        self.cursor[0] += width + PADDING;
+        self.row_height = self.row_height.max(height);
+
+        Some(region)
+    }
+
+    pub fn upload(&self, queue: &wgpu::Queue, region: Region, data: &[u8]) {
+        if region.width == 0 || region.height == 0 {
+            return;
+        }
+
+        let bytes_per_pixel = self.format.block_copy_size(None).unwrap_or(1);
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: region.x,
+                    y: region.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(region.width * bytes_per_pixel),
+                rows_per_image: Some(region.height),
+            },
+            wgpu::Extent3d {
+                width: region.width,
+                height: region.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Replaces the texture with an empty one twice as big, unless that's more than the device
+    /// supports. Every region is forgotten either way.
+    pub fn grow(&mut self, device: &wgpu::Device) -> bool {
+        let size = self.size * 2;
+        if size > self.max_size {
+            return false;
+        }
+
+        *self = Self::new(device, self.format, size);
+        true
+    }
+
+    /// Binds the texture at 0 and `sampler` at 1, which is what both atlases use
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        label: &str,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some(label),
+        })
+    }
+
+    /// Forgets every region. Their texels stay around until overwritten
+    pub fn clear(&mut self) {
+        self.cursor = [PADDING, PADDING];
+        self.row_height = 0;
+    }
+
+    /// Normalized texture coordinates of `region` as x, y, width, height
+    pub fn uv(&self, region: Region) -> [f32; 4] {
+        let size = self.size as f32;
+
+        [
+            region.x as f32 / size,
+            region.y as f32 / size,
+            region.width as f32 / size,
+            region.height as f32 / size,
+        ]
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{
+    config,
+    rectangle::{
+        border::BorderStyle,
+        image::{ImageRepeat, ImageSize},
+        outline::OutlineStyle,
+        Rectangle,
+    },
+    text::Text,
+    tree,
+};
+
+/// Builds what the bar shows for `config`, laid out at `width` x `height`
+pub fn build(config: &config::Config, width: f32, height: f32) -> tree::Tree {
+    let color = config.background_color;
+
+    let mut root = tree::Tree::new(
+        Rectangle::default()
+            .set_size(width, height)
+            .set_background_color(color[0], color[1], color[2], color[3]),
+    );
+
+    root.set_direction(match config.position {
+        config::Position::Top | config::Position::Bottom => tree::layout::Direction::Row,
+        config::Position::Left | config::Position::Right => tree::layout::Direction::Column,
+    });
+
+    match config.contents.as_ref() {
+        Some(contents) => {
+            let sections = [
+                (tree::Section::Start, &contents.start),
+                (tree::Section::Center, &contents.center),
+                (tree::Section::End, &contents.end),
+            ];
+
+            for (section, items) in sections {
+                items
+                    .iter()
+                    .for_each(|item| root.section_mut(section).add_child(node(item)));
+            }
+        }
+        None => demo(&mut root),
+    }
+
+    root.layout();
+
+    root
+}
+
+fn node(item: &config::Item) -> tree::Node {
+    match item {
+        config::Item::Text {
+            content,
+            size,
+            weight,
+            color,
+        } => {
+            let mut text = Text::new(content).set_color(1.0, 1.0, 1.0, 1.0);
+            if let Some(size) = size {
+                text = text.set_size(*size);
+            }
+            if let Some(weight) = weight {
+                text = text.set_weight(*weight);
+            }
+            if let Some([r, g, b, a]) = *color {
+                text = text.set_color(r, g, b, a);
+            }
+            text.into()
+        }
+        config::Item::Image {
+            path,
+            width,
+            height,
+        } => Rectangle::default()
+            .set_size(*width, *height)
+            .set_background_image(path)
+            .set_background_size(ImageSize::Contain)
+            .set_background_repeat(ImageRepeat::NoRepeat)
+            .into(),
+    }
+}
+
+/// Shown until the config says what to show instead
+fn demo(root: &mut tree::Tree) {
+    root.section_mut(tree::Section::Start).add_child(
+        Rectangle::default()
+            .set_background_color(0.0, 0.0, 1.0, 1.0)
+            .set_size(100.0, 100.0)
+            .set_border_radius(0.0, 10.0, 30.0, 50.0)
+            .set_border_color(1.0, 1.0, 1.0, 1.0)
+            .set_border_size(2.0, 2.0, 2.0, 2.0),
+    );
+
+    root.section_mut(tree::Section::Start).add_child(
+        Rectangle::default()
+            .set_background_color(1.0, 0.0, 0.0, 1.0)
+            .set_size(300.0, 300.0)
+            .set_border_radius(10.0, 10.0, 10.0, 10.0),
+    );
+
+    root.section_mut(tree::Section::Center).add_child(
+        Text::new("status bar")
+            .set_size(24.0)
+            .set_weight(700)
+            .set_color(1.0, 1.0, 1.0, 1.0),
+    );
+
+    root.section_mut(tree::Section::Center).add_child(
+        tree::Node::new(
+            Rectangle::default()
+                .set_background_color(0.0, 1.0, 0.0, 1.0)
+                .set_size(100.0, 100.0)
+                .set_border_radius(55.0, 55.0, 55.0, 55.0)
+                .set_boxshadow_offset(0.0, 10.0)
+                .set_boxshadow_color(1.0, 1.0, 0.0, 1.0)
+                .set_boxshadow_softness(30.0),
+        )
+        .set_style(tree::State::Hover, |rectangle| {
+            rectangle.set_background_color(0.5, 1.0, 0.5, 1.0)
+        })
+        .set_style(tree::State::Active, |rectangle| {
+            rectangle.set_background_color(0.0, 0.6, 0.0, 1.0)
+        })
+        .set_on_click(|_, click| {
+            log::info!("{:?} click at {}, {}", click.button, click.x, click.y);
+        })
+        .set_on_scroll(|_, scroll| {
+            if scroll.steps != [0, 0] {
+                log::info!("scrolled {:?} steps", scroll.steps);
+            }
+        }),
+    );
+
+    root.section_mut(tree::Section::End).add_child(
+        Rectangle::default()
+            .set_background_color(0.0, 1.0, 0.0, 1.0)
+            .set_size(100.0, 100.0)
+            .set_border_radius(10.0, 10.0, 10.0, 10.0)
+            .set_border_size(0.0, 5.0, 10.0, 15.0)
+            .set_border_color(1.0, 1.0, 0.0, 1.0)
+            .set_outline_width(5.0)
+            .set_outline_color(1.0, 0.0, 0.0, 1.0)
+            .set_outline_offset(50.0)
+            .set_boxshadow_offset(0.0, 10.0)
+            .set_boxshadow_color(1.0, 1.0, 0.0, 1.0)
+            .set_boxshadow_softness(30.0),
+    );
+
+    root.section_mut(tree::Section::End).add_child(
+        Rectangle::default()
+            .set_background_color(0.0, 1.0, 0.0, 1.0)
+            .set_size(100.0, 100.0)
+            .set_border_radius(10.0, 10.0, 10.0, 10.0)
+            .set_border_size(4.0, 4.0, 4.0, 4.0)
+            .set_border_colors(
+                [1.0, 1.0, 0.0, 1.0],
+                [1.0, 0.0, 1.0, 1.0],
+                [0.0, 1.0, 1.0, 1.0],
+                [1.0, 1.0, 1.0, 1.0],
+            )
+            .set_border_style(BorderStyle::Dashed)
+            .set_outline_style(OutlineStyle::Dotted)
+            .set_outline_width(5.0)
+            .set_outline_color(1.0, 0.0, 0.0, 1.0)
+            .set_outline_offset(50.0)
+            .set_boxshadow_offset(0.0, 10.0)
+            .set_boxshadow_color(1.0, 1.0, 0.0, 1.0)
+            .set_boxshadow_softness(30.0),
+    );
+}
@@ -1,13 +1,16 @@
 use crate::math::{self, Matrix};
 use std::{
+    marker::PhantomData,
+    mem::offset_of,
     ops::{Deref, RangeBounds},
-    rc::Rc,
 };
 use wgpu::{util::DeviceExt, BufferAddress};
 
 pub struct Buffer<T> {
     buffer: wgpu::Buffer,
-    vertices: Rc<[T]>,
+    /// Elements in use, which may be fewer than fit
+    len: u32,
+    _marker: PhantomData<T>,
 }
 
 impl<T> Buffer<T> {
@@ -21,12 +24,13 @@ impl<T> Buffer<T> {
                 usage,
                 contents: bytemuck::cast_slice(data),
             }),
-            vertices: data.into(),
+            len: data.len() as u32,
+            _marker: PhantomData,
         }
     }
 
     pub fn size(&self) -> u32 {
-        self.vertices.len() as u32
+        self.len
     }
 
     pub fn slice<S>(&self, bounds: S) -> wgpu::BufferSlice<'_>
@@ -66,11 +70,7 @@ pub struct VertexBuffer(Buffer<Vertex>);
 
 impl VertexBuffer {
     pub fn new(device: &wgpu::Device, vertices: &[Vertex]) -> Self {
-        VertexBuffer(Buffer::new(
-            device,
-            wgpu::BufferUsages::VERTEX,
-            vertices.into(),
-        ))
+        VertexBuffer(Buffer::new(device, wgpu::BufferUsages::VERTEX, vertices))
     }
 }
 
@@ -96,6 +96,16 @@ impl Deref for IndexBuffer {
     }
 }
 
+/// What the fragment shader draws for an instance
+#[repr(u32)]
+pub enum InstanceKind {
+    Rectangle,
+    /// Coverage mask from the glyph atlas tinted with the instance color
+    Glyph,
+    /// Rectangle with its background image sampled from the image atlas
+    Image,
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Debug)]
 pub struct Instance {
@@ -103,32 +113,85 @@ pub struct Instance {
     pub color: [f32; 4],
     pub border_radius: [f32; 4],
     pub border_size: [f32; 4],
-    pub border_color: [f32; 4],
-    pub outline: [f32; 2],
+    /// Top, right, bottom and left color as packed 8 bit RGBA
+    pub border_color: [u32; 4],
     pub outline_color: [f32; 4],
     pub filter: [f32; 4],
+    pub outline: [f32; 2],
     pub grayscale: f32,
-    pub scale: [f32; 2],
     pub rotation: f32,
+    pub scale: [f32; 2],
     pub translate: [f32; 2],
     pub skew: [f32; 2],
+    pub image_spacing: [f32; 2],
+    /// Atlas region to sample as x, y, width, height
+    pub texture: [f32; 4],
+    /// Offset and size of a single background image tile, relative to the padding box
+    pub image: [f32; 4],
+    /// Box shadow x and y offset, softness, and how much further than the outline the quad
+    /// reaches to fit the shadow
+    pub shadow: [f32; 4],
+    pub shadow_color: [f32; 4],
+    /// Kind, image repeat with a bit per axis, border style with the outline style in the second
+    /// byte, and inset shadow
+    pub flags: [u32; 4],
+}
+
+const fn attribute(
+    shader_location: u32,
+    format: wgpu::VertexFormat,
+    offset: usize,
+) -> wgpu::VertexAttribute {
+    wgpu::VertexAttribute {
+        format,
+        offset: offset as wgpu::BufferAddress,
+        shader_location,
+    }
 }
 
 impl Instance {
-    const ATTRIBS: [wgpu::VertexAttribute; 13] = wgpu::vertex_attr_array![
-        1 => Float32x4,
-        2 => Float32x4,
-        3 => Float32x4,
-        4 => Float32x4,
-        5 => Float32x4,
-        6 => Float32x2,
-        7 => Float32x4,
-        8 => Float32x4,
-        9 => Float32,
-        10 => Float32x2,
-        11 => Float32,
-        12 => Float32x2,
-        13 => Float32x2
+    // Adapters may only support 16 vertex attributes, so consecutive scalar and vec2 fields are
+    // read as a single vec4 and unpacked in the shader
+    const ATTRIBS: [wgpu::VertexAttribute; 15] = [
+        attribute(
+            1,
+            wgpu::VertexFormat::Float32x4,
+            offset_of!(Self, dimensions),
+        ),
+        attribute(2, wgpu::VertexFormat::Float32x4, offset_of!(Self, color)),
+        attribute(
+            3,
+            wgpu::VertexFormat::Float32x4,
+            offset_of!(Self, border_radius),
+        ),
+        attribute(
+            4,
+            wgpu::VertexFormat::Float32x4,
+            offset_of!(Self, border_size),
+        ),
+        attribute(
+            5,
+            wgpu::VertexFormat::Uint32x4,
+            offset_of!(Self, border_color),
+        ),
+        attribute(
+            6,
+            wgpu::VertexFormat::Float32x4,
+            offset_of!(Self, outline_color),
+        ),
+        attribute(7, wgpu::VertexFormat::Float32x4, offset_of!(Self, filter)),
+        attribute(8, wgpu::VertexFormat::Float32x4, offset_of!(Self, outline)),
+        attribute(9, wgpu::VertexFormat::Float32x4, offset_of!(Self, scale)),
+        attribute(10, wgpu::VertexFormat::Float32x4, offset_of!(Self, skew)),
+        attribute(11, wgpu::VertexFormat::Float32x4, offset_of!(Self, texture)),
+        attribute(12, wgpu::VertexFormat::Float32x4, offset_of!(Self, image)),
+        attribute(13, wgpu::VertexFormat::Float32x4, offset_of!(Self, shadow)),
+        attribute(
+            14,
+            wgpu::VertexFormat::Float32x4,
+            offset_of!(Self, shadow_color),
+        ),
+        attribute(15, wgpu::VertexFormat::Uint32x4, offset_of!(Self, flags)),
     ];
 
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
@@ -140,11 +203,76 @@ impl Instance {
     }
 }
 
+/// Kept between frames. Only instances that changed are written, and the buffer is only
+/// reallocated when there are more instances than ever before
 pub struct InstanceBuffer(Buffer<Instance>);
 
 impl InstanceBuffer {
-    pub fn new(device: &wgpu::Device, instances: &[Instance]) -> InstanceBuffer {
-        InstanceBuffer(Buffer::new(device, wgpu::BufferUsages::VERTEX, instances))
+    pub fn new(device: &wgpu::Device, capacity: u32) -> InstanceBuffer {
+        InstanceBuffer(Buffer {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Instance buffer"),
+                size: capacity.max(1) as u64 * size_of::<Instance>() as u64,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            len: 0,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn capacity(&self) -> u32 {
+        (self.0.buffer.size() / size_of::<Instance>() as u64) as u32
+    }
+
+    /// Makes the buffer hold `instances`, given that it currently holds `previous`. Runs of
+    /// changed instances are written with one call each.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        previous: &[Instance],
+        instances: &[Instance],
+    ) {
+        let len = instances.len() as u32;
+
+        // Everything has to be written to a new buffer anyway
+        if len > self.capacity() {
+            *self = Self::new(device, len.next_power_of_two());
+            self.write(queue, 0, instances);
+            self.0.len = len;
+            return;
+        }
+
+        let mut start = None;
+        for (index, instance) in instances.iter().enumerate() {
+            let changed = previous
+                .get(index)
+                .is_none_or(|old| bytemuck::bytes_of(old) != bytemuck::bytes_of(instance));
+
+            match (changed, start) {
+                (true, None) => start = Some(index),
+                (false, Some(first)) => {
+                    self.write(queue, first, &instances[first..index]);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(first) = start {
+            self.write(queue, first, &instances[first..]);
+        }
+
+        self.0.len = len;
+    }
+
+    fn write(&self, queue: &wgpu::Queue, first: usize, instances: &[Instance]) {
+        queue.write_buffer(
+            &self.0.buffer,
+            (first * size_of::<Instance>()) as BufferAddress,
+            bytemuck::cast_slice(instances),
+        );
     }
 }
 
@@ -158,33 +286,35 @@ impl Deref for InstanceBuffer {
 pub struct ProjectionUniform {
     pub buffer: wgpu::Buffer,
     pub projection: math::Mat4,
-    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub scale: f32,
     pub bind_group: wgpu::BindGroup,
 }
 
 impl ProjectionUniform {
-    pub fn new(device: &wgpu::Device, left: f32, right: f32, top: f32, bottom: f32) -> Self {
+    /// Maps logical coordinates to clip space, `scale` being the physical pixels per logical one
+    /// the target is drawn at
+    pub fn new(
+        device: &wgpu::Device,
+        left: f32,
+        right: f32,
+        top: f32,
+        bottom: f32,
+        scale: f32,
+    ) -> Self {
         let projection = math::Mat4::projection(left, right, top, bottom);
 
+        // The matrix followed by the scale, padded to the 16 byte alignment of the struct
+        let mut contents = [0.0; 20];
+        contents[..16].copy_from_slice(projection.as_flattened());
+        contents[16] = scale;
+
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Buffer"),
             usage: wgpu::BufferUsages::UNIFORM,
-            contents: bytemuck::cast_slice(&projection),
+            contents: bytemuck::cast_slice(&contents),
         });
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-            label: Some("bind group layout"),
-        });
+        let bind_group_layout = Self::create_bind_group_layout(device);
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
@@ -197,8 +327,24 @@ impl ProjectionUniform {
         Self {
             buffer,
             projection,
+            scale,
             bind_group,
-            bind_group_layout,
         }
     }
+
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("bind group layout"),
+        })
+    }
 }
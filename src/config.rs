@@ -0,0 +1,515 @@
+use notify::Watcher as _;
+use serde::Deserialize;
+use status_bar::renderer;
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::{self, Layer};
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Position {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Graphics API to draw with. `Auto` tries Vulkan, then GL.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Auto,
+    Vulkan,
+    Gl,
+}
+
+impl Backend {
+    /// Backends to try, in order
+    pub fn order(self) -> &'static [wgpu::Backends] {
+        match self {
+            Backend::Auto => renderer::AUTO,
+            Backend::Vulkan => &[wgpu::Backends::VULKAN],
+            Backend::Gl => &[wgpu::Backends::GL],
+        }
+    }
+}
+
+/// Which adapter to prefer when there's more than one, e.g. an integrated and a discrete GPU
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerPreference {
+    #[default]
+    None,
+    Low,
+    High,
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(preference: PowerPreference) -> Self {
+        match preference {
+            PowerPreference::None => wgpu::PowerPreference::None,
+            PowerPreference::Low => wgpu::PowerPreference::LowPower,
+            PowerPreference::High => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct Margin {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+/// Something shown in a section of the bar, e.g. `{ type = "text", content = "12:00" }`
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Item {
+    Text {
+        content: String,
+        size: Option<f32>,
+        weight: Option<u16>,
+        color: Option<[f32; 4]>,
+    },
+    /// PNG, JPEG or SVG file drawn at `width` x `height`
+    Image {
+        path: PathBuf,
+        width: f32,
+        height: f32,
+    },
+}
+
+/// Items in each section of the bar, in order along it
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Contents {
+    pub start: Vec<Item>,
+    pub center: Vec<Item>,
+    pub end: Vec<Item>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub size: u32,
+    pub margin: Margin,
+    pub position: Position,
+    #[serde(with = "layer")]
+    pub layer: zwlr_layer_shell_v1::Layer,
+    pub background_color: [f32; 4],
+    pub enable: bool,
+    /// Lets the bar take keyboard focus when clicked, to move between clickable nodes with Tab
+    /// and the arrow keys and activate them with Enter. Clicks and scrolls only see which
+    /// modifiers are held while the bar has focus.
+    pub keyboard_navigation: bool,
+    /// What the bar shows, the built-in demo when unset
+    pub contents: Option<Contents>,
+    /// Read once at startup, overridden by `WGPU_BACKEND`
+    pub backend: Backend,
+    /// Read once at startup, overridden by `WGPU_POWER_PREF`
+    pub power_preference: PowerPreference,
+    /// Overrides keyed by output name (e.g. `DP-1`) or a glob matching it (e.g. `HDMI-*`)
+    #[serde(rename = "output")]
+    pub outputs: BTreeMap<String, OutputConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            size: 500,
+            margin: Margin::default(),
+            position: Position::Left,
+            layer: Layer::Top,
+            background_color: [0.0, 0.0, 0.0, 0.0],
+            enable: true,
+            keyboard_navigation: false,
+            contents: None,
+            backend: Backend::default(),
+            power_preference: PowerPreference::default(),
+            outputs: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub size: Option<u32>,
+    pub margin: Option<Margin>,
+    pub position: Option<Position>,
+    #[serde(with = "layer::option")]
+    pub layer: Option<zwlr_layer_shell_v1::Layer>,
+    pub background_color: Option<[f32; 4]>,
+    pub enable: Option<bool>,
+    pub keyboard_navigation: Option<bool>,
+    /// Replaces the contents as a whole, sections aren't merged
+    pub contents: Option<Contents>,
+}
+
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, err) => write!(f, "failed to read {}: {err}", path.display()),
+            // toml errors already point at the offending line and column
+            Error::Parse(path, err) => write!(f, "invalid config {}: {err}", path.display()),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from `path`, or from `$XDG_CONFIG_HOME/status-bar/config.toml` when no
+    /// path is given. A missing default config is not an error and yields `Config::default()`.
+    pub fn load(path: Option<PathBuf>) -> Result<Self, Error> {
+        match path {
+            Some(path) => Self::read(path, true),
+            None => match default_path() {
+                Some(path) => Self::read(path, false),
+                None => Ok(Self::default()),
+            },
+        }
+    }
+
+    /// Reads `path`, which may only be missing if it isn't `required`
+    fn read(path: PathBuf, required: bool) -> Result<Self, Error> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default())
+            }
+            Err(err) => return Err(Error::Io(path, err)),
+        };
+
+        toml::from_str(&contents).map_err(|err| Error::Parse(path, err))
+    }
+}
+
+impl Config {
+    /// Resolves the config for the output called `name`, returning `None` if the bar is disabled
+    /// on it. Glob overrides are applied first, so an exact name match always wins.
+    pub fn for_output(&self, name: Option<&str>) -> Option<Self> {
+        let mut config = Self {
+            outputs: BTreeMap::new(),
+            ..self.clone()
+        };
+
+        if let Some(name) = name {
+            let (exact, globs): (Vec<_>, Vec<_>) = self
+                .outputs
+                .iter()
+                .filter(|(pattern, _)| glob_match(pattern, name))
+                .partition(|(pattern, _)| pattern.as_str() == name);
+
+            globs
+                .into_iter()
+                .chain(exact)
+                .for_each(|(_, output)| config.merge(output));
+        }
+
+        config.enable.then_some(config)
+    }
+
+    fn merge(&mut self, output: &OutputConfig) {
+        if let Some(size) = output.size {
+            self.size = size;
+        }
+        if let Some(margin) = output.margin {
+            self.margin = margin;
+        }
+        if let Some(position) = output.position {
+            self.position = position;
+        }
+        if let Some(layer) = output.layer {
+            self.layer = layer;
+        }
+        if let Some(background_color) = output.background_color {
+            self.background_color = background_color;
+        }
+        if let Some(enable) = output.enable {
+            self.enable = enable;
+        }
+        if let Some(keyboard_navigation) = output.keyboard_navigation {
+            self.keyboard_navigation = keyboard_navigation;
+        }
+        if let Some(contents) = output.contents.as_ref() {
+            self.contents = Some(contents.clone());
+        }
+    }
+}
+
+/// Matches `name` against a pattern where `*` matches any run of characters and `?` matches a
+/// single one
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("status-bar").join("config.toml"))
+}
+
+/// Watches the config file for changes. The parent directory is watched instead of the file
+/// itself, as most editors save by replacing the file rather than writing to it.
+pub struct Watcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl Watcher {
+    /// Calls `on_change` from the watcher's thread whenever the config file is modified
+    pub fn new<F: Fn() + Send + 'static>(path: PathBuf, on_change: F) -> notify::Result<Self> {
+        let file_name = path.file_name().map(ToOwned::to_owned);
+
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) => {
+                    if !event.kind.is_access()
+                        && event
+                            .paths
+                            .iter()
+                            .any(|path| path.file_name() == file_name.as_deref())
+                    {
+                        on_change();
+                    }
+                }
+                Err(err) => log::warn!("config watcher: {err}"),
+            })?;
+
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+// zwlr_layer_shell_v1::Layer is generated by wayland-scanner, so it can't derive Deserialize
+mod layer {
+    use serde::{de, Deserialize, Deserializer};
+    use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::Layer;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Layer, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let layer = String::deserialize(deserializer)?;
+
+        match layer.as_str() {
+            "background" => Ok(Layer::Background),
+            "bottom" => Ok(Layer::Bottom),
+            "top" => Ok(Layer::Top),
+            "overlay" => Ok(Layer::Overlay),
+            _ => Err(de::Error::unknown_variant(
+                &layer,
+                &["background", "bottom", "top", "overlay"],
+            )),
+        }
+    }
+
+    pub mod option {
+        use serde::Deserializer;
+        use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::Layer;
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Layer>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `contents` to a file of its own in the temporary directory
+    fn write(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("status-bar-{}-{name}.toml", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn missing_default_file_is_the_default() {
+        let path = std::env::temp_dir().join("status-bar-does-not-exist.toml");
+
+        let config = Config::read(path, false).ok().unwrap();
+        assert_eq!(config.size, Config::default().size);
+        assert!(matches!(config.position, Position::Left));
+    }
+
+    #[test]
+    fn missing_given_file_is_an_error() {
+        let path = std::env::temp_dir().join("status-bar-does-not-exist.toml");
+
+        assert!(matches!(Config::load(Some(path)), Err(Error::Io(..))));
+    }
+
+    #[test]
+    fn invalid_file_is_an_error() {
+        let path = write("invalid", "size = \"big\"\n");
+
+        let result = Config::read(path.clone(), false);
+        fs::remove_file(path).unwrap();
+
+        // Points at the offending line rather than panicking
+        let Err(err @ Error::Parse(..)) = result else {
+            panic!("expected a parse error");
+        };
+        assert!(err.to_string().contains("line 1"), "{err}");
+    }
+
+    #[test]
+    fn valid_file_is_read() {
+        let path = write("valid", "size = 40\nposition = \"top\"\n");
+
+        let config = Config::read(path.clone(), true).ok().unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(config.size, 40);
+        assert!(matches!(config.position, Position::Top));
+        assert!(config.enable);
+    }
+
+    fn parse(toml: &str) -> Config {
+        toml::from_str(toml).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn center_text(config: &Config) -> Option<&str> {
+        match config.contents.as_ref()?.center.first()? {
+            Item::Text { content, .. } => Some(content),
+            Item::Image { .. } => None,
+        }
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("HDMI-*", "HDMI-A-1"));
+        assert!(glob_match("DP-?", "DP-1"));
+        assert!(glob_match("*", "eDP-1"));
+        assert!(glob_match("*-1", "eDP-1"));
+        assert!(!glob_match("DP-?", "DP-10"));
+        assert!(!glob_match("HDMI-*", "DP-1"));
+        assert!(!glob_match("DP-1", "eDP-1"));
+    }
+
+    #[test]
+    fn exact_match_wins_over_glob() {
+        let config = parse(
+            r#"
+            size = 30
+
+            [output."DP-1"]
+            size = 50
+
+            [output."DP-*"]
+            size = 40
+            "#,
+        );
+
+        assert_eq!(config.for_output(Some("DP-1")).unwrap().size, 50);
+        assert_eq!(config.for_output(Some("DP-2")).unwrap().size, 40);
+        assert_eq!(config.for_output(Some("HDMI-A-1")).unwrap().size, 30);
+        // Before the name is known only the top level applies
+        assert_eq!(config.for_output(None).unwrap().size, 30);
+    }
+
+    #[test]
+    fn disabled_output() {
+        let config = parse(
+            r#"
+            [output."HDMI-*"]
+            enable = false
+
+            [output."HDMI-A-2"]
+            enable = true
+            "#,
+        );
+
+        assert!(config.for_output(Some("HDMI-A-1")).is_none());
+        assert!(config.for_output(Some("HDMI-A-2")).is_some());
+        assert!(config.for_output(Some("DP-1")).is_some());
+
+        let disabled = parse("enable = false\n[output.\"DP-1\"]\nenable = true\n");
+        assert!(disabled.for_output(Some("DP-2")).is_none());
+        assert!(disabled.for_output(Some("DP-1")).is_some());
+    }
+
+    #[test]
+    fn partial_overrides_merge() {
+        let config = parse(
+            r#"
+            size = 30
+            position = "top"
+            layer = "bottom"
+
+            [contents]
+            center = [{ type = "text", content = "everywhere" }]
+
+            [output."DP-*"]
+            position = "bottom"
+            margin = { left = 4 }
+
+            [output."DP-1"]
+            size = 50
+            contents = { center = [{ type = "text", content = "DP-1 only" }] }
+            "#,
+        );
+
+        let dp1 = config.for_output(Some("DP-1")).unwrap();
+        assert_eq!(dp1.size, 50);
+        assert!(matches!(dp1.position, Position::Bottom));
+        assert_eq!(dp1.layer, Layer::Bottom);
+        assert_eq!((dp1.margin.left, dp1.margin.top), (4, 0));
+        assert_eq!(center_text(&dp1), Some("DP-1 only"));
+
+        let dp2 = config.for_output(Some("DP-2")).unwrap();
+        assert_eq!(dp2.size, 30);
+        assert!(matches!(dp2.position, Position::Bottom));
+        assert_eq!(center_text(&dp2), Some("everywhere"));
+    }
+}
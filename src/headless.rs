@@ -0,0 +1,210 @@
+use crate::{
+    buffers,
+    renderer::{self, Renderer},
+    tree,
+};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+// sRGB like the surfaces, so colors come out the same as on screen
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+pub enum Error {
+    NoAdapter,
+    Device(wgpu::RequestDeviceError),
+    Save(PathBuf, image::ImageError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoAdapter => write!(f, "no adapter available"),
+            Error::Device(err) => write!(f, "failed to request device: {err}"),
+            Error::Save(path, err) => write!(f, "failed to save {}: {err}", path.display()),
+        }
+    }
+}
+
+/// Draws trees to images, without a Wayland connection
+pub struct Headless {
+    renderer: Renderer,
+    instance_buffer: buffers::InstanceBuffer,
+}
+
+impl Headless {
+    /// Takes whatever adapter there is, which can be a software one like lavapipe or llvmpipe.
+    /// `WGPU_BACKEND` picks the backend, like for the bar itself.
+    pub fn new() -> Result<Self, Error> {
+        Self::with_adapter(false)
+    }
+
+    /// Only takes a software adapter, so images come out the same on any machine with the same
+    /// driver
+    pub fn software() -> Result<Self, Error> {
+        Self::with_adapter(true)
+    }
+
+    fn with_adapter(force_fallback_adapter: bool) -> Result<Self, Error> {
+        let options = wgpu::RequestAdapterOptions {
+            force_fallback_adapter,
+            ..Default::default()
+        };
+
+        let from_env = wgpu::util::backend_bits_from_env();
+        let order = match from_env.as_ref() {
+            Some(backends) => std::slice::from_ref(backends),
+            None => renderer::AUTO,
+        };
+
+        let (_, adapter) =
+            renderer::request_adapter(order, &options, None).ok_or(Error::NoAdapter)?;
+
+        let (device, queue) = renderer::request_device(&adapter).map_err(Error::Device)?;
+
+        let mut renderer = Renderer::new(device, queue);
+        renderer.prepare_pipeline(FORMAT);
+
+        Ok(Self {
+            instance_buffer: buffers::InstanceBuffer::new(&renderer.device, 256),
+            renderer,
+        })
+    }
+
+    /// Lays `tree` out at `width` x `height` and draws it with `scale` pixels per unit. The
+    /// image has straight alpha, like PNG expects.
+    pub fn render(
+        &mut self,
+        tree: &mut tree::Tree,
+        width: u32,
+        height: u32,
+        scale: f32,
+    ) -> image::RgbaImage {
+        let data = std::mem::take(&mut tree.data);
+        tree.data = data.set_size(width as f32, height as f32);
+        tree.layout();
+
+        // The instance buffer may hold another tree
+        tree.invalidate();
+        self.renderer
+            .prepare(tree, &mut self.instance_buffer, scale);
+
+        let device = &self.renderer.device;
+
+        let size = wgpu::Extent3d {
+            width: ((width as f32 * scale).ceil() as u32).max(1),
+            height: ((height as f32 * scale).ceil() as u32).max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Copied rows have to be aligned, the padding is skipped when reading them back
+        let row = size.width * 4;
+        let padded_row = row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless readback"),
+            size: padded_row as u64 * size.height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let projection_uniform =
+            buffers::ProjectionUniform::new(device, 0.0, width as f32, 0.0, height as f32, scale);
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        self.renderer.render(
+            &mut encoder,
+            &view,
+            FORMAT,
+            &projection_uniform,
+            tree,
+            &self.instance_buffer,
+        );
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        self.renderer.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("Failed to map readback buffer")
+        });
+        device.poll(wgpu::Maintain::Wait);
+
+        let pixels = slice
+            .get_mapped_range()
+            .chunks_exact(padded_row as usize)
+            .flat_map(|padded| padded[..row as usize].chunks_exact(4))
+            .flat_map(demultiply)
+            .collect();
+
+        image::RgbaImage::from_raw(size.width, size.height, pixels).unwrap()
+    }
+}
+
+/// Draws `tree` like `Headless::render` and saves it as a PNG
+pub fn render_to_png(
+    tree: &mut tree::Tree,
+    width: u32,
+    height: u32,
+    scale: f32,
+    path: &Path,
+) -> Result<(), Error> {
+    Headless::new()?
+        .render(tree, width, height, scale)
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|err| Error::Save(path.to_path_buf(), err))
+}
+
+// Blending leaves sRGB encoded premultiplied colors, which have to be divided in linear space
+fn demultiply(pixel: &[u8]) -> [u8; 4] {
+    let alpha = pixel[3];
+    if alpha == 0 || alpha == 255 {
+        return [pixel[0], pixel[1], pixel[2], alpha];
+    }
+
+    let to_linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        match c <= 0.04045 {
+            true => c / 12.92,
+            false => ((c + 0.055) / 1.055).powf(2.4),
+        }
+    };
+    let to_srgb = |c: f32| {
+        let c = c.clamp(0.0, 1.0);
+        let c = match c <= 0.0031308 {
+            true => c * 12.92,
+            false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+        };
+        (c * 255.0).round() as u8
+    };
+
+    let a = alpha as f32 / 255.0;
+    [
+        to_srgb(to_linear(pixel[0]) / a),
+        to_srgb(to_linear(pixel[1]) / a),
+        to_srgb(to_linear(pixel[2]) / a),
+        alpha,
+    ]
+}
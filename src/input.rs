@@ -0,0 +1,71 @@
+//! Input events as nodes see them, already resolved to the node they're for
+
+/// Touchscreens have no buttons, a tap clicks with `Left` and a long-press with `Right`, like
+/// pressing and holding opens context menus on most touch interfaces
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
+    /// Any other button, by its Linux input event code
+    Other(u32),
+}
+
+impl From<u32> for Button {
+    /// Maps a Linux input event code, as sent by `wl_pointer`
+    fn from(code: u32) -> Self {
+        match code {
+            0x110 => Button::Left,
+            0x111 => Button::Right,
+            0x112 => Button::Middle,
+            code => Button::Other(code),
+        }
+    }
+}
+
+/// Modifier keys held down when the event happened. Compositors only tell the client with
+/// keyboard focus about them, so they're only known while the bar has it, which needs
+/// `keyboard_navigation`. Otherwise every modifier reads as released.
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Click {
+    pub button: Button,
+    /// Empty unless the bar has keyboard focus, see `Modifiers`
+    pub modifiers: Modifiers,
+    /// Position relative to the top left corner of the node handling the click
+    pub x: f32,
+    pub y: f32,
+}
+
+/// What a scroll came from, e.g. to ignore smooth scrolling where only whole steps make sense
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScrollSource {
+    Wheel,
+    /// A touchpad, with fingers on it
+    Finger,
+    /// Like a touchpad, but without the finger lifting to say when it stopped
+    Continuous,
+    WheelTilt,
+}
+
+/// Horizontal then vertical, positive values scroll right and down
+#[derive(Clone, Copy, Debug)]
+pub struct Scroll {
+    pub source: ScrollSource,
+    /// Whole steps, a wheel click each. Smooth scrolling adds up its distance into steps.
+    pub steps: [i32; 2],
+    /// Distance in logical pixels
+    pub delta: [f32; 2],
+    /// Empty unless the bar has keyboard focus, see `Modifiers`
+    pub modifiers: Modifiers,
+    /// Position relative to the top left corner of the node handling the scroll
+    pub x: f32,
+    pub y: f32,
+}
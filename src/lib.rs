@@ -0,0 +1,11 @@
+//! Everything that turns a tree of rectangles and text into draw calls, independent of Wayland
+
+pub mod atlas;
+pub mod buffers;
+pub mod headless;
+pub mod input;
+pub mod math;
+pub mod rectangle;
+pub mod renderer;
+pub mod text;
+pub mod tree;
@@ -1,66 +1,314 @@
-pub mod buffers;
-pub mod math;
+mod bar;
+mod config;
 mod output;
-mod rectangle;
 mod seat;
 mod wgpu_state;
 
+use calloop::{
+    channel,
+    signals::{Signal, Signals},
+    EventLoop, LoopHandle,
+};
+use calloop_wayland_source::WaylandSource;
+use status_bar::{buffers, headless, input, rectangle, text, tree};
+use std::path::{Path, PathBuf};
 use wayland_client::{
     delegate_noop,
     protocol::{wl_compositor, wl_output, wl_registry, wl_seat},
     Connection, Dispatch, QueueHandle,
 };
-use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1;
-use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::{self, Layer};
+use wayland_protocols::{
+    wp::{
+        fractional_scale::v1::client::wp_fractional_scale_manager_v1,
+        viewporter::client::wp_viewporter,
+    },
+    xdg::xdg_output::zv1::client::zxdg_output_manager_v1,
+};
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1;
 use wgpu_state::WgpuState;
 
-struct StatusBar {
-    output_manager: Option<zxdg_output_manager_v1::ZxdgOutputManagerV1>,
+/// Globals bars are created with, bound as the compositor announces them
+#[derive(Default)]
+struct Globals {
     compositor: Option<wl_compositor::WlCompositor>,
     layer_shell: Option<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
-    seat: Option<seat::Seat>,
+    output_manager: Option<zxdg_output_manager_v1::ZxdgOutputManagerV1>,
+    /// Fractional scaling needs both, otherwise bars fall back to integer buffer scales
+    fractional_scale_manager: Option<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1>,
+    viewporter: Option<wp_viewporter::WpViewporter>,
+}
+
+struct StatusBar {
+    globals: Globals,
+    seats: Vec<seat::Seat>,
     outputs: Vec<output::Output>,
+    /// Outputs announced before the globals needed to put a bar on them
+    pending_outputs: Vec<output::Output>,
+    /// Set after the initial burst of globals, a missing xdg-output manager won't turn up anymore
+    globals_announced: bool,
     wgpu: wgpu_state::WgpuState,
+    /// For timers, like the one telling a long-press from a tap
+    loop_handle: LoopHandle<'static, StatusBar>,
+    config: config::Config,
+    config_path: Option<PathBuf>,
     exit: bool,
 }
 
 impl StatusBar {
-    fn new(conn: &Connection) -> Self {
+    fn new(
+        conn: &Connection,
+        loop_handle: LoopHandle<'static, StatusBar>,
+        config: config::Config,
+        config_path: Option<PathBuf>,
+    ) -> Self {
         Self {
-            seat: None,
-            compositor: None,
-            output_manager: None,
-            layer_shell: None,
+            globals: Globals::default(),
+            seats: Vec::new(),
             outputs: Vec::new(),
-            wgpu: WgpuState::new(conn),
+            pending_outputs: Vec::new(),
+            globals_announced: false,
+            wgpu: WgpuState::new(conn, &config),
+            loop_handle,
+            config,
+            config_path,
             exit: false,
         }
     }
 
-    fn render(&mut self) {
-        self.outputs.iter_mut().for_each(|output| output.render());
+    fn reload_config(&mut self, qh: &QueueHandle<Self>) {
+        let config = match config::Config::load(self.config_path.clone()) {
+            Ok(config) => config,
+            Err(err) => {
+                log::error!("{err}, keeping previous config");
+                return;
+            }
+        };
+
+        log::info!("config reloaded");
+
+        self.outputs
+            .iter_mut()
+            .for_each(|output| output.apply_config(&config, &self.globals, qh, &mut self.wgpu));
+        self.config = config;
+    }
+
+    /// Puts a bar on the outputs that were announced before every global needed for it was bound
+    fn setup_outputs(&mut self, qh: &QueueHandle<Self>) {
+        if self.globals.compositor.is_none() || self.globals.layer_shell.is_none() {
+            return;
+        }
+
+        // xdg-output and fractional scaling are optional, but preferred when the compositor has
+        // them
+        if !self.globals_announced
+            && (self.globals.output_manager.is_none()
+                || self.globals.fractional_scale_manager.is_none()
+                || self.globals.viewporter.is_none())
+        {
+            return;
+        }
+
+        for mut output in self.pending_outputs.drain(..) {
+            if let Some(output_manager) = self.globals.output_manager.as_ref() {
+                output.get_xdg_output(output_manager, qh);
+            }
+
+            output.apply_config(&self.config, &self.globals, qh, &mut self.wgpu);
+
+            self.outputs.push(output);
+        }
+    }
+
+    /// Releases whatever belonged to a global that went away, like an unplugged monitor
+    fn remove_global(&mut self, name: u32) {
+        if let Some(index) = self
+            .outputs
+            .iter()
+            .position(|output| output.info.id == name)
+        {
+            self.outputs.swap_remove(index).destroy();
+        } else if let Some(index) = self
+            .pending_outputs
+            .iter()
+            .position(|output| output.info.id == name)
+        {
+            self.pending_outputs.swap_remove(index).destroy();
+        } else if let Some(index) = self.seats.iter().position(|seat| seat.id == name) {
+            self.seats.swap_remove(index).release(&self.loop_handle);
+        }
+    }
+
+    /// Redraws the outputs whose tree changed since they were last drawn
+    fn render(&mut self, qh: &QueueHandle<Self>) {
+        if self.wgpu.renderer.is_lost() {
+            log::warn!("recreating GPU state after losing the device");
+            self.wgpu.recreate(&self.config);
+            self.outputs
+                .iter_mut()
+                .for_each(|output| output.recreate_gpu(&mut self.wgpu));
+        } else if std::mem::take(&mut self.wgpu.reselected) {
+            // Surfaces made with the previous adapter can't be drawn to with the new device
+            self.outputs
+                .iter_mut()
+                .for_each(|output| output.recreate_gpu(&mut self.wgpu));
+        }
+
+        self.outputs
+            .iter_mut()
+            .for_each(|output| output.render(qh, &self.loop_handle, &mut self.wgpu));
     }
 }
 
+struct Args {
+    config: Option<PathBuf>,
+    /// Draws the bar to this PNG and exits instead of running
+    render_to: Option<PathBuf>,
+    size: Option<(u32, u32)>,
+    scale: f32,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = std::env::args().skip(1);
+        let mut config = None;
+        let mut render_to = None;
+        let mut size = None;
+        let mut scale = 1.0;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-c" | "--config" => {
+                    let path = args.next().ok_or(format!("{arg} requires a path"))?;
+                    config = Some(PathBuf::from(path));
+                }
+                "--render-to" => {
+                    let path = args.next().ok_or(format!("{arg} requires a path"))?;
+                    render_to = Some(PathBuf::from(path));
+                }
+                "--size" => {
+                    let value = args.next().ok_or(format!("{arg} requires WIDTHxHEIGHT"))?;
+                    let parsed = value.split_once('x').and_then(|(width, height)| {
+                        Some((width.parse().ok()?, height.parse().ok()?))
+                    });
+                    size = Some(parsed.ok_or(format!("invalid size: {value}"))?);
+                }
+                "--scale" => {
+                    let value = args.next().ok_or(format!("{arg} requires a number"))?;
+                    scale = value
+                        .parse()
+                        .ok()
+                        .filter(|scale: &f32| *scale > 0.0)
+                        .ok_or(format!("invalid scale: {value}"))?;
+                }
+                _ => return Err(format!("unknown argument: {arg}")),
+            }
+        }
+
+        Ok(Self {
+            config,
+            render_to,
+            size,
+            scale,
+        })
+    }
+}
+
+/// Draws the bar the way it would look on a 1920x1080 output, unless `size` says otherwise
+fn render_to(path: &Path, config: &config::Config, size: Option<(u32, u32)>, scale: f32) {
+    let (width, height) = size.unwrap_or(match config.position {
+        config::Position::Top | config::Position::Bottom => (1920, config.size),
+        config::Position::Left | config::Position::Right => (config.size, 1080),
+    });
+
+    let mut tree = bar::build(config, width as f32, height as f32);
+
+    headless::render_to_png(&mut tree, width, height, scale, path).unwrap_or_else(|err| {
+        log::error!("{err}");
+        std::process::exit(1);
+    });
+}
+
 fn main() {
     env_logger::init();
 
+    let args = Args::parse().unwrap_or_else(|err| {
+        log::error!("{err}");
+        std::process::exit(1);
+    });
+
+    let config = config::Config::load(args.config.clone()).unwrap_or_else(|err| {
+        log::error!("{err}");
+        std::process::exit(1);
+    });
+
+    if let Some(path) = args.render_to {
+        render_to(&path, &config, args.size, args.scale);
+        return;
+    }
+
+    let mut event_loop: EventLoop<StatusBar> =
+        EventLoop::try_new().expect("Failed to create event loop");
+    let handle = event_loop.handle();
+
+    // Signals are blocked on this thread so the loop can read them, which has to happen before
+    // any other thread is spawned for them to inherit the mask
+    let signals =
+        Signals::new(&[Signal::SIGINT, Signal::SIGTERM]).expect("Failed to block signals");
+    handle
+        .insert_source(signals, |event, _, status_bar| {
+            log::info!("received {:?}, exiting", event.signal());
+            status_bar.exit = true;
+        })
+        .expect("Failed to insert signal source");
+
     let conn = Connection::connect_to_env().expect("Connection to wayland failed");
     let display = conn.display();
 
     let mut event_queue = conn.new_event_queue();
     let qh = event_queue.handle();
 
-    let mut status_bar = StatusBar::new(&conn);
+    let mut status_bar = StatusBar::new(&conn, handle.clone(), config, args.config.clone());
 
     _ = display.get_registry(&qh, ());
-    event_queue.dispatch_pending(&mut status_bar).unwrap();
     event_queue.roundtrip(&mut status_bar).unwrap();
 
-    while !status_bar.exit {
-        event_queue.dispatch_pending(&mut status_bar).unwrap();
-        status_bar.render();
-    }
+    status_bar.globals_announced = true;
+    status_bar.setup_outputs(&qh);
+
+    WaylandSource::new(conn, event_queue)
+        .insert(handle.clone())
+        .expect("Failed to insert wayland source");
+
+    // The watcher runs on its own thread, reloads happen on the loop
+    let (reload, reloads) = channel::channel();
+    let reload_qh = qh.clone();
+    handle
+        .insert_source(reloads, move |event, _, status_bar| {
+            if let channel::Event::Msg(()) = event {
+                status_bar.reload_config(&reload_qh);
+            }
+        })
+        .expect("Failed to insert config reload source");
+
+    let _watcher = args.config.or_else(config::default_path).and_then(|path| {
+        config::Watcher::new(path, move || _ = reload.send(()))
+            .inspect_err(|err| log::warn!("config hot-reload disabled: {err}"))
+            .ok()
+    });
+
+    let signal = event_loop.get_signal();
+
+    // Sleeps until there's something to do, then draws whatever it changed
+    event_loop
+        .run(None, &mut status_bar, |status_bar| {
+            if status_bar.exit {
+                signal.stop();
+                return;
+            }
+
+            status_bar.render(&qh);
+        })
+        .expect("Event loop failed");
 }
 
 impl Dispatch<wl_registry::WlRegistry, ()> for StatusBar {
@@ -77,88 +325,80 @@ impl Dispatch<wl_registry::WlRegistry, ()> for StatusBar {
                 name,
                 interface,
                 version,
-            } => match interface.as_str() {
-                "wl_compositor" => {
-                    state.compositor = Some(registry.bind::<wl_compositor::WlCompositor, _, _>(
-                        name,
-                        version,
-                        qh,
-                        (),
-                    ));
-                }
-                "zxdg_output_manager_v1" => {
-                    state.output_manager = Some(
-                        registry.bind::<zxdg_output_manager_v1::ZxdgOutputManagerV1, _, _>(
-                            name,
-                            version,
-                            qh,
-                            (),
-                        ),
-                    );
-                }
-                "zwlr_layer_shell_v1" => {
-                    state.layer_shell = Some(
-                        registry.bind::<zwlr_layer_shell_v1::ZwlrLayerShellV1, _, _>(
+            } => {
+                match interface.as_str() {
+                    "wl_compositor" => {
+                        state.globals.compositor =
+                            Some(registry.bind::<wl_compositor::WlCompositor, _, _>(
+                                name,
+                                version,
+                                qh,
+                                (),
+                            ));
+                    }
+                    "zxdg_output_manager_v1" => {
+                        state.globals.output_manager = Some(
+                            registry.bind::<zxdg_output_manager_v1::ZxdgOutputManagerV1, _, _>(
+                                name,
+                                version,
+                                qh,
+                                (),
+                            ),
+                        );
+                    }
+                    "zwlr_layer_shell_v1" => {
+                        state.globals.layer_shell = Some(
+                            registry.bind::<zwlr_layer_shell_v1::ZwlrLayerShellV1, _, _>(
+                                name,
+                                version,
+                                qh,
+                                (),
+                            ),
+                        );
+                    }
+                    "wp_fractional_scale_manager_v1" => {
+                        state.globals.fractional_scale_manager = Some(
+                            registry
+                                .bind::<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1, _, _>(
+                                    name,
+                                    version,
+                                    qh,
+                                    (),
+                                ),
+                        );
+                    }
+                    "wp_viewporter" => {
+                        state.globals.viewporter =
+                            Some(registry.bind::<wp_viewporter::WpViewporter, _, _>(
+                                name,
+                                version,
+                                qh,
+                                (),
+                            ));
+                    }
+                    "wl_seat" => {
+                        let seat = registry.bind::<wl_seat::WlSeat, _, _>(name, version, qh, ());
+                        state.seats.push(seat::Seat::new(name, seat));
+                    }
+                    "wl_output" => {
+                        // Version 4 adds the name, used when there's no xdg-output
+                        let output = registry.bind::<wl_output::WlOutput, _, _>(
                             name,
-                            version,
+                            version.min(4),
                             qh,
                             (),
-                        ),
-                    );
-                }
-                "wl_seat" => {
-                    let seat = registry.bind::<wl_seat::WlSeat, _, _>(name, version, qh, ());
-
-                    state.seat = Some(seat::Seat {
-                        seat,
-                        pointer: None,
-                    });
-                }
-                "wl_output" => {
-                    let output = registry.bind::<wl_output::WlOutput, _, _>(name, version, qh, ());
-
-                    let surface = state.compositor.as_ref().unwrap().create_surface(qh, ());
-
-                    let layer_surface = state.layer_shell.as_ref().unwrap().get_layer_surface(
-                        &surface,
-                        Some(&output),
-                        Layer::Top,
-                        "status bar".to_string(),
-                        qh,
-                        (),
-                    );
-
-                    let xdg_output =
+                        );
                         state
-                            .output_manager
-                            .as_ref()
-                            .unwrap()
-                            .get_xdg_output(&output, qh, ());
-
-                    state.outputs.push(output::Output::new(
-                        output,
-                        xdg_output,
-                        surface,
-                        layer_surface,
-                        name,
-                        state.wgpu.raw_display_handle,
-                        &state.wgpu.instance,
-                    ));
-                }
-                _ => {}
-            },
-            wl_registry::Event::GlobalRemove { name } => {
-                let index = state
-                    .outputs
-                    .iter()
-                    .enumerate()
-                    .find(|(_, output)| output.info.id == name)
-                    .map(|(index, _)| index);
-
-                if let Some(index) = index {
-                    state.outputs.swap_remove(index);
+                            .pending_outputs
+                            .push(output::Output::new(output, name));
+                    }
+                    _ => {}
                 }
+
+                // Any of them may have been the last one outputs were waiting for
+                state.setup_outputs(qh);
             }
+            wl_registry::Event::GlobalRemove { name } => state.remove_global(name),
             _ => unreachable!(),
         }
     }
@@ -167,3 +407,5 @@ impl Dispatch<wl_registry::WlRegistry, ()> for StatusBar {
 delegate_noop!(StatusBar: zxdg_output_manager_v1::ZxdgOutputManagerV1);
 delegate_noop!(StatusBar: zwlr_layer_shell_v1::ZwlrLayerShellV1);
 delegate_noop!(StatusBar: wl_compositor::WlCompositor);
+delegate_noop!(StatusBar: wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1);
+delegate_noop!(StatusBar: wp_viewporter::WpViewporter);
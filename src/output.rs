@@ -1,15 +1,16 @@
 mod surface;
-mod tree;
-
-use crate::{buffers, rectangle::Rectangle, StatusBar};
-use raw_window_handle::RawDisplayHandle;
-use surface::config;
-use wayland_client::{
-    protocol::{wl_output, wl_surface},
-    Connection, Dispatch, QueueHandle,
+
+use crate::{config, wgpu_state, Globals, StatusBar};
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
 };
-use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1;
-use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1;
+use std::time::Duration;
+use wayland_client::{protocol::wl_output, Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols::xdg::xdg_output::zv1::client::{zxdg_output_manager_v1, zxdg_output_v1};
+
+/// How long to wait before drawing again after the swapchain had no texture to give
+const RETRY: Duration = Duration::from_millis(100);
 
 pub struct OutputInfo {
     name: Option<String>,
@@ -32,76 +33,198 @@ impl OutputInfo {
 }
 
 pub struct Output {
-    surface: surface::Surface,
+    /// `None` while the bar is disabled on this output
+    pub surface: Option<surface::Surface>,
     output: wl_output::WlOutput,
-    xdg_output: zxdg_output_v1::ZxdgOutputV1,
+    /// `None` if the compositor has no xdg-output manager, in which case the name comes from
+    /// `wl_output` and the size from the layer surface
+    xdg_output: Option<zxdg_output_v1::ZxdgOutputV1>,
     pub info: OutputInfo,
 }
 
 impl Output {
-    pub fn new(
-        output: wl_output::WlOutput,
-        xdg_output: zxdg_output_v1::ZxdgOutputV1,
-        surface: wl_surface::WlSurface,
-        layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
-        id: u32,
-        raw_display_handle: RawDisplayHandle,
-        instance: &wgpu::Instance,
-    ) -> Self {
-        let surface = surface::Surface::new(layer_surface, surface, raw_display_handle, instance);
-
+    pub fn new(output: wl_output::WlOutput, id: u32) -> Self {
         Self {
-            xdg_output,
+            xdg_output: None,
             output,
             info: OutputInfo::new(id),
-            surface,
+            surface: None,
+        }
+    }
+
+    pub fn get_xdg_output(
+        &mut self,
+        output_manager: &zxdg_output_manager_v1::ZxdgOutputManagerV1,
+        qh: &QueueHandle<StatusBar>,
+    ) {
+        self.xdg_output = Some(output_manager.get_xdg_output(&self.output, qh, ()));
+    }
+
+    /// Resolves `config` for this output and applies it, creating or destroying the bar surface
+    /// if the output got enabled or disabled
+    pub fn apply_config(
+        &mut self,
+        config: &config::Config,
+        globals: &Globals,
+        qh: &QueueHandle<StatusBar>,
+        wgpu: &mut wgpu_state::WgpuState,
+    ) {
+        let Some(config) = config.for_output(self.info.name.as_deref()) else {
+            if let Some(surface) = self.surface.take() {
+                surface.destroy();
+            }
+            return;
+        };
+
+        match self.surface.as_mut() {
+            Some(surface) => {
+                surface.config = config;
+                surface.apply_config();
+            }
+            None => {
+                self.surface = Some(surface::Surface::new(
+                    &self.output,
+                    self.info.scale,
+                    globals,
+                    qh,
+                    wgpu,
+                    config,
+                ));
+            }
+        }
+
+        self.update_size();
+    }
+
+    /// Destroys the bar and releases the output, once the output is gone
+    pub fn destroy(self) {
+        if let Some(surface) = self.surface {
+            surface.destroy();
         }
+
+        if let Some(xdg_output) = self.xdg_output {
+            xdg_output.destroy();
+        }
+        release(&self.output);
+    }
+
+    fn update_size(&self) {
+        let Some(surface) = self.surface.as_ref() else {
+            return;
+        };
+
+        let config = &surface.config;
+
+        // Without xdg-output the size stays 0, which stretches the bar between the edges it's
+        // anchored to and leaves the compositor to tell the size in the configure
+        let (width, height) = match config.position {
+            config::Position::Top => (self.info.width as u32, config.size),
+            config::Position::Bottom => (self.info.width as u32, config.size),
+            config::Position::Left => (config.size, self.info.height as u32),
+            config::Position::Right => (config.size, self.info.height as u32),
+        };
+
+        surface.layer_surface.set_size(width, height);
+        surface.surface.commit();
     }
 
-    pub fn render(&mut self) {
-        let surface_texture = self
-            .surface
-            .wgpu
-            .surface
-            .get_current_texture()
-            .expect("failed to acquire next swapchain texture");
+    /// Draws the tree if it changed since it was last drawn and the compositor is ready for a
+    /// new frame
+    pub fn render(
+        &mut self,
+        qh: &QueueHandle<StatusBar>,
+        loop_handle: &LoopHandle<'static, StatusBar>,
+        wgpu: &mut wgpu_state::WgpuState,
+    ) {
+        let Some(surface) = self.surface.as_mut() else {
+            return;
+        };
+
+        let renderer = &mut wgpu.renderer;
+        if !surface.configured
+            || surface.frame_pending
+            || !renderer.needs_prepare(&surface.background)
+        {
+            return;
+        }
+
+        if !renderer.prepare(
+            &mut surface.background,
+            &mut surface.wgpu.instance_buffer,
+            surface.wgpu.projection_uniform.scale,
+        ) {
+            return;
+        }
+
+        let surface_texture = match surface.wgpu.acquire(&renderer.device) {
+            Ok(surface_texture) => surface_texture,
+            Err(err) => {
+                // The instances were already written, drawing them all again is what's left
+                log::warn!("skipping frame: {err}");
+                surface.background.invalidate();
+
+                // Nothing else may wake the loop up to draw again, an idle bar would stay stale.
+                // Waking it is enough, the loop draws whatever is outdated each time.
+                if !surface.retry_pending {
+                    let wl_surface = surface.surface.clone();
+                    surface.retry_pending = loop_handle
+                        .insert_source(Timer::from_duration(RETRY), move |_, _, state| {
+                            state
+                                .outputs
+                                .iter_mut()
+                                .filter_map(|output| output.surface.as_mut())
+                                .filter(|bar| bar.surface == wl_surface)
+                                .for_each(|bar| bar.retry_pending = false);
+                            TimeoutAction::Drop
+                        })
+                        .inspect_err(|err| log::warn!("can't retry the frame: {err}"))
+                        .is_ok();
+                }
+
+                return;
+            }
+        };
         let texture_view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let mut encoder = self
-            .surface
-            .wgpu
-            .device
-            .create_command_encoder(&Default::default());
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &texture_view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
-
-        render_pass.set_pipeline(&self.surface.wgpu.render_pipeline);
-        render_pass.set_bind_group(0, &self.surface.wgpu.projection_uniform.bind_group, &[]);
-
-        self.surface.background.render(
-            &self.surface.wgpu.device,
-            &mut render_pass,
-            &self.surface.wgpu.index_buffer,
+        let mut encoder = renderer.device.create_command_encoder(&Default::default());
+        renderer.render(
+            &mut encoder,
+            &texture_view,
+            surface.wgpu.config.format,
+            &surface.wgpu.projection_uniform,
+            &surface.background,
+            &surface.wgpu.instance_buffer,
         );
 
-        drop(render_pass); // Drop renderpass and release mutable borrow on encoder
+        // Both are double buffered state, committed by presenting
+        surface.damage(&surface.background.damage());
+        surface.request_frame(qh);
+
+        renderer.queue.submit(Some(encoder.finish()));
 
-        self.surface.wgpu.queue.submit(Some(encoder.finish()));
+        let suboptimal = surface_texture.suboptimal;
         surface_texture.present();
+
+        // Still presentable, but the next frame will be better off with a fresh configuration
+        if suboptimal {
+            surface.wgpu.reconfigure(&renderer.device);
+        }
+    }
+
+    /// Gives the surface new GPU resources after `wgpu` was recreated
+    pub fn recreate_gpu(&mut self, wgpu: &mut wgpu_state::WgpuState) {
+        if let Some(surface) = self.surface.as_mut() {
+            surface.recreate_gpu(wgpu);
+        }
+    }
+}
+
+/// Outputs can only be released since version 3, older ones are just forgotten
+fn release(output: &wl_output::WlOutput) {
+    if output.version() >= 3 {
+        output.release();
     }
 }
 
@@ -112,32 +235,29 @@ impl Dispatch<zxdg_output_v1::ZxdgOutputV1, ()> for StatusBar {
         event: zxdg_output_v1::Event,
         _: &(),
         _: &Connection,
-        _: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
     ) {
-        let output = state
+        // Events may still be in flight for an output that was removed
+        let Some(output) = state
             .outputs
             .iter_mut()
-            .find(|output| output.xdg_output == *xdg_output)
-            .unwrap(); // Can't be called if this xdg_output wasn't created
+            .find(|output| output.xdg_output.as_ref() == Some(xdg_output))
+        else {
+            return;
+        };
 
         match event {
-            zxdg_output_v1::Event::Name { name } => output.info.name = Some(name),
+            zxdg_output_v1::Event::Name { name } => {
+                output.info.name = Some(name);
+
+                // Overrides can only be matched once the name is known
+                output.apply_config(&state.config, &state.globals, qh, &mut state.wgpu);
+            }
             zxdg_output_v1::Event::LogicalSize { width, height } => {
                 output.info.width = width;
                 output.info.height = height;
 
-                let (width, height) = match output.surface.config.position {
-                    config::Position::Top => (width as u32, output.surface.config.size),
-                    config::Position::Bottom => (width as u32, output.surface.config.size),
-                    config::Position::Left => (output.surface.config.size, height as u32),
-                    config::Position::Right => (output.surface.config.size, height as u32),
-                };
-
-                output
-                    .surface
-                    .layer_surface
-                    .set_size(width as u32, height as u32);
-                output.surface.surface.commit();
+                output.update_size();
             }
             _ => {}
         }
@@ -151,17 +271,41 @@ impl Dispatch<wl_output::WlOutput, ()> for StatusBar {
         event: wl_output::Event,
         _: &(),
         _: &Connection,
-        _: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
     ) {
-        let output = state
+        // Outputs still waiting for globals have their info filled in, to use once they're set up
+        let (output, pending) = match state
             .outputs
             .iter_mut()
             .find(|output| output.output == *wl_output)
-            .unwrap(); // Can't be called if this wl_output wasn't created
+        {
+            Some(output) => (output, false),
+            None => match state
+                .pending_outputs
+                .iter_mut()
+                .find(|output| output.output == *wl_output)
+            {
+                Some(output) => (output, true),
+                None => return,
+            },
+        };
 
         match event {
             wl_output::Event::Scale { factor } => {
                 output.info.scale = factor;
+
+                if let Some(surface) = output.surface.as_mut() {
+                    surface.set_output_scale(&state.wgpu.renderer.device, factor);
+                }
+            }
+            // Since version 4. xdg-output names are the same, but preferred as more compositors
+            // have them
+            wl_output::Event::Name { name } if output.xdg_output.is_none() => {
+                output.info.name = Some(name);
+
+                if !pending {
+                    output.apply_config(&state.config, &state.globals, qh, &mut state.wgpu);
+                }
             }
             _ => {}
         }
@@ -1,122 +1,165 @@
-pub mod config;
 pub mod wgpu_surface;
 
-use crate::{buffers, rectangle::Rectangle, StatusBar};
-use raw_window_handle::RawDisplayHandle;
-use wayland_client::{protocol::wl_surface, Connection, Dispatch, QueueHandle};
-use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::{self, Anchor};
+use crate::{bar, config, rectangle::Rectangle, wgpu_state, Globals, StatusBar};
+use wayland_client::{
+    delegate_noop,
+    protocol::{wl_callback, wl_output, wl_surface},
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+use wayland_protocols::wp::{
+    fractional_scale::v1::client::wp_fractional_scale_v1, viewporter::client::wp_viewport,
+};
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::{
+    self, Anchor, KeyboardInteractivity,
+};
 
-use super::tree;
+use crate::tree;
 
 pub struct Surface {
     pub wgpu: wgpu_surface::WgpuSurface,
     pub layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
     pub surface: wl_surface::WlSurface,
+    /// Both `None` when the compositor can't do fractional scaling, the buffer scale is used then
+    fractional_scale: Option<wp_fractional_scale_v1::WpFractionalScaleV1>,
+    viewport: Option<wp_viewport::WpViewport>,
+    /// Physical pixels per logical one
+    scale: f32,
+    /// Logical size from the last configure
+    size: (u32, u32),
     pub config: config::Config,
     pub background: tree::Tree,
+    /// Nothing can be drawn before the first configure sizes the surface
+    pub configured: bool,
+    /// Set between presenting a frame and the compositor saying it's a good time for the next one
+    pub frame_pending: bool,
+    /// Set while a timer is waiting to try again after a frame couldn't be drawn
+    pub retry_pending: bool,
 }
 
 impl Surface {
     pub fn new(
-        layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
-        surface: wl_surface::WlSurface,
-        raw_display_handle: RawDisplayHandle,
-        instance: &wgpu::Instance,
+        output: &wl_output::WlOutput,
+        output_scale: i32,
+        globals: &Globals,
+        qh: &QueueHandle<StatusBar>,
+        wgpu: &mut wgpu_state::WgpuState,
+        config: config::Config,
     ) -> Self {
-        let mut config = config::Config::default();
-        config.position = config::Position::Left;
-        config.background_color = [0.0, 0.0, 0.0, 0.0];
+        // Outputs only get a bar once both are bound
+        let surface = globals.compositor.as_ref().unwrap().create_surface(qh, ());
+
+        // The preferred scale is sent before the first configure, until then it's 1
+        let (fractional_scale, viewport, scale) =
+            match (&globals.fractional_scale_manager, &globals.viewporter) {
+                (Some(fractional_scale_manager), Some(viewporter)) => (
+                    Some(fractional_scale_manager.get_fractional_scale(&surface, qh, ())),
+                    Some(viewporter.get_viewport(&surface, qh, ())),
+                    1.0,
+                ),
+                _ => {
+                    surface.set_buffer_scale(output_scale);
+                    (None, None, output_scale as f32)
+                }
+            };
+
+        let layer_surface = globals.layer_shell.as_ref().unwrap().get_layer_surface(
+            &surface,
+            Some(output),
+            config.layer,
+            "status bar".to_string(),
+            qh,
+            (),
+        );
 
         layer_surface.set_size(1, 1);
         layer_surface.set_anchor(Anchor::Top);
         surface.commit();
 
         let mut surface = Self {
-            wgpu: wgpu_surface::WgpuSurface::new(&surface, raw_display_handle, instance),
+            wgpu: wgpu_surface::WgpuSurface::new(&surface, wgpu),
             layer_surface,
             surface,
+            fractional_scale,
+            viewport,
+            scale,
+            size: (0, 0),
             config,
             background: tree::Tree::new(Rectangle::default()),
+            configured: false,
+            frame_pending: false,
+            retry_pending: false,
         };
 
         surface.apply_config();
 
-        surface.background.add_child(
-            Rectangle::default()
-                .set_background_color(0.0, 0.0, 1.0, 1.0)
-                .set_size(100.0, 100.0)
-                .set_coordinates(100.0, 700.0)
-                .set_border_radius(0.0, 10.0, 30.0, 50.0)
-                .set_border_color(1.0, 1.0, 1.0, 1.0)
-                .set_border_size(2.0, 2.0, 2.0, 2.0),
-        );
+        surface
+    }
 
-        surface.background.add_child(
-            Rectangle::default()
-                .set_background_color(1.0, 0.0, 0.0, 1.0)
-                .set_size(300.0, 300.0)
-                .set_coordinates(200.0, 100.0)
-                .set_border_radius(10.0, 10.0, 10.0, 10.0),
-        );
+    pub fn destroy(self) {
+        // The wgpu surface holds a pointer to the wl_surface, so it has to go first
+        drop(self.wgpu);
 
-        surface.background.add_child(
-            Rectangle::default()
-                .set_background_color(0.0, 1.0, 0.0, 1.0)
-                .set_size(100.0, 100.0)
-                .set_coordinates(10.0, 100.0)
-                .set_border_radius(55.0, 55.0, 55.0, 55.0)
-                .set_boxshadow_offset(0.0, 10.0)
-                .set_boxshadow_color(1.0, 1.0, 0.0, 1.0)
-                .set_boxshadow_softness(30.0),
-        );
+        if let Some(fractional_scale) = self.fractional_scale {
+            fractional_scale.destroy();
+        }
+        if let Some(viewport) = self.viewport {
+            viewport.destroy();
+        }
+        self.layer_surface.destroy();
+        self.surface.destroy();
+    }
 
-        surface.background.add_child(
-            Rectangle::default()
-                .set_background_color(0.0, 1.0, 0.0, 1.0)
-                .set_size(100.0, 100.0)
-                .set_coordinates(100.0, 500.0)
-                .set_border_radius(10.0, 10.0, 10.0, 10.0)
-                .set_border_size(0.0, 5.0, 10.0, 15.0)
-                .set_border_color(1.0, 1.0, 0.0, 1.0)
-                .set_outline_width(5.0)
-                .set_outline_color(1.0, 0.0, 0.0, 1.0)
-                .set_outline_offset(50.0)
-                .set_boxshadow_offset(0.0, 10.0)
-                .set_boxshadow_color(1.0, 1.0, 0.0, 1.0)
-                .set_boxshadow_softness(30.0),
-        );
+    /// Sizes the surface to `width` x `height` logical pixels
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let background = std::mem::take(&mut self.background.data);
+        self.background.data = background.set_size(width as f32, height as f32);
+        self.background.layout();
 
-        surface.background.add_child(
-            Rectangle::default()
-                .set_background_color(0.0, 1.0, 0.0, 1.0)
-                .set_size(100.0, 100.0)
-                .set_coordinates(100.0, 500.0)
-                .set_border_radius(10.0, 10.0, 10.0, 10.0)
-                .set_border_size(0.0, 5.0, 10.0, 15.0)
-                .set_border_color(1.0, 1.0, 0.0, 1.0)
-                .set_outline_width(5.0)
-                .set_outline_color(1.0, 0.0, 0.0, 1.0)
-                .set_outline_offset(50.0)
-                .set_boxshadow_offset(0.0, 10.0)
-                .set_boxshadow_color(1.0, 1.0, 0.0, 1.0)
-                .set_boxshadow_softness(30.0),
-        );
+        self.size = (width, height);
+        if let Some(viewport) = self.viewport.as_ref() {
+            viewport.set_destination(width as i32, height as i32);
+        }
+        self.wgpu.resize(device, width, height, self.scale);
 
-        surface
+        self.configured = true;
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
-        let background = std::mem::take(&mut self.background.data);
-        self.background.data = background.set_size(width as f32, height as f32);
+    /// Draws at `scale` physical pixels per logical one from the next frame on
+    fn set_scale(&mut self, device: &wgpu::Device, scale: f32) {
+        if scale == self.scale {
+            return;
+        }
 
-        self.wgpu.resize(width, height);
-        self.wgpu.projection_uniform = buffers::ProjectionUniform::new(
-            &self.wgpu.device,
-            0.0,
-            width as f32,
-            0.0,
-            height as f32,
-        );
+        self.scale = scale;
+        if self.viewport.is_none() {
+            self.surface.set_buffer_scale(scale as i32);
+        }
+
+        if self.configured {
+            self.wgpu.resize(device, self.size.0, self.size.1, scale);
+        }
+
+        // Nothing moved in logical coordinates, but every pixel is different
+        self.background.invalidate();
+    }
+
+    /// Integer scale of the output, used when the compositor doesn't say which scale it prefers
+    /// for the surface itself
+    pub fn set_output_scale(&mut self, device: &wgpu::Device, factor: i32) {
+        if self.fractional_scale.is_none() && self.surface.version() < 6 {
+            self.set_scale(device, factor as f32);
+        }
+    }
+
+    /// Replaces the GPU side of the surface after `wgpu` was recreated, everything is drawn again
+    pub fn recreate_gpu(&mut self, wgpu: &mut wgpu_state::WgpuState) {
+        self.wgpu = wgpu_surface::WgpuSurface::new(&self.surface, wgpu);
+        if self.configured {
+            self.wgpu
+                .resize(&wgpu.renderer.device, self.size.0, self.size.1, self.scale);
+        }
+
+        self.background.invalidate();
     }
 
     pub fn apply_config(&mut self) {
@@ -131,6 +174,18 @@ impl Surface {
         self.layer_surface
             .set_exclusive_zone(self.config.size as i32);
         self.layer_surface.set_layer(self.config.layer);
+
+        // On demand focus arrived in version 4, before that it's all or nothing
+        let keyboard_interactivity = match self.config.keyboard_navigation {
+            true if self.layer_surface.version() >= 4 => KeyboardInteractivity::OnDemand,
+            true => {
+                log::warn!("keyboard navigation needs layer shell version 4");
+                KeyboardInteractivity::None
+            }
+            false => KeyboardInteractivity::None,
+        };
+        self.layer_surface
+            .set_keyboard_interactivity(keyboard_interactivity);
         self.layer_surface.set_margin(
             self.config.margin.top as i32,
             self.config.margin.right as i32,
@@ -138,22 +193,122 @@ impl Surface {
             self.config.margin.left as i32,
         );
 
-        let color = self.config.background_color;
-        let background = std::mem::take(&mut self.background.data);
-        self.background.data =
-            background.set_background_color(color[0], color[1], color[2], color[3]);
+        self.build_tree();
+    }
+
+    fn build_tree(&mut self) {
+        let extents = self.background.data.get_extents();
+        self.background = bar::build(&self.config, extents.width, extents.height);
+    }
+
+    /// Marks what changed in the next frame. Regions are in logical pixels, damaged in buffer
+    /// pixels when the surface is new enough.
+    pub fn damage(&self, damage: &tree::Damage) {
+        let buffer = self.surface.version() >= 4;
+        let scale = if buffer { self.scale } else { 1.0 };
+
+        let damage_buffer = |x: i32, y: i32, width: i32, height: i32| {
+            if buffer {
+                self.surface.damage_buffer(x, y, width, height);
+            } else {
+                self.surface.damage(x, y, width, height);
+            }
+        };
+
+        match damage {
+            tree::Damage::Full => damage_buffer(0, 0, i32::MAX, i32::MAX),
+            tree::Damage::Regions(regions) => regions.iter().for_each(|extents| {
+                // Antialiasing reaches a little past the edges
+                let x = (extents.x * scale).floor() as i32 - 1;
+                let y = (extents.y * scale).floor() as i32 - 1;
+                let right = ((extents.x + extents.width) * scale).ceil() as i32 + 1;
+                let bottom = ((extents.y + extents.height) * scale).ceil() as i32 + 1;
+
+                damage_buffer(x, y, right - x, bottom - y);
+            }),
+        }
+    }
+
+    pub fn request_frame(&mut self, qh: &QueueHandle<StatusBar>) {
+        self.surface.frame(qh, self.surface.clone());
+        self.frame_pending = true;
     }
 }
 
 impl Dispatch<wl_surface::WlSurface, ()> for StatusBar {
     fn event(
-        _: &mut Self,
-        _: &wl_surface::WlSurface,
-        _: wl_surface::Event,
+        state: &mut Self,
+        wl_surface: &wl_surface::WlSurface,
+        event: wl_surface::Event,
         _: &(),
         _: &Connection,
         _: &QueueHandle<Self>,
     ) {
+        // Since version 6, replaces guessing from the outputs the surface is on
+        let wl_surface::Event::PreferredBufferScale { factor } = event else {
+            return;
+        };
+
+        if let Some(surface) = state
+            .outputs
+            .iter_mut()
+            .filter_map(|output| output.surface.as_mut())
+            .find(|bar| bar.surface == *wl_surface && bar.fractional_scale.is_none())
+        {
+            surface.set_scale(&state.wgpu.renderer.device, factor as f32);
+        }
+    }
+}
+
+impl Dispatch<wp_fractional_scale_v1::WpFractionalScaleV1, ()> for StatusBar {
+    fn event(
+        state: &mut Self,
+        fractional_scale: &wp_fractional_scale_v1::WpFractionalScaleV1,
+        event: wp_fractional_scale_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let wp_fractional_scale_v1::Event::PreferredScale { scale } = event else {
+            return;
+        };
+
+        if let Some(surface) = state
+            .outputs
+            .iter_mut()
+            .filter_map(|output| output.surface.as_mut())
+            .find(|bar| bar.fractional_scale.as_ref() == Some(fractional_scale))
+        {
+            // The numerator of a fraction over 120
+            surface.set_scale(&state.wgpu.renderer.device, scale as f32 / 120.0);
+        }
+    }
+}
+
+delegate_noop!(StatusBar: ignore wp_viewport::WpViewport);
+
+impl Dispatch<wl_callback::WlCallback, wl_surface::WlSurface> for StatusBar {
+    fn event(
+        state: &mut Self,
+        _: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        surface: &wl_surface::WlSurface,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let wl_callback::Event::Done { .. } = event else {
+            return;
+        };
+
+        // Anything that changed while waiting gets drawn once the loop is idle
+        if let Some(surface) = state
+            .outputs
+            .iter_mut()
+            .filter_map(|output| output.surface.as_mut())
+            .find(|bar| bar.surface == *surface)
+        {
+            surface.frame_pending = false;
+        }
     }
 }
 
@@ -166,22 +321,33 @@ impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for StatusBar {
         _: &Connection,
         _: &QueueHandle<Self>,
     ) {
-        let output = state
-            .outputs
-            .iter_mut()
-            .find(|output| output.surface.layer_surface == *layer_surface)
-            .unwrap(); // Can't be called if this layer_surface wasn't created
-
-        let zwlr_layer_surface_v1::Event::Configure {
-            serial,
-            width,
-            height,
-        } = event
-        else {
+        // The surface may have been destroyed with events still in flight
+        let Some(output) = state.outputs.iter_mut().find(|output| {
+            output
+                .surface
+                .as_ref()
+                .is_some_and(|surface| surface.layer_surface == *layer_surface)
+        }) else {
             return;
         };
 
-        output.surface.layer_surface.ack_configure(serial);
-        output.surface.resize(width, height);
+        match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                let surface = output.surface.as_mut().unwrap();
+                surface.layer_surface.ack_configure(serial);
+                surface.resize(&state.wgpu.renderer.device, width, height);
+            }
+            // E.g. when the output is going away, the surface can't be used anymore
+            zwlr_layer_surface_v1::Event::Closed => {
+                if let Some(surface) = output.surface.take() {
+                    surface.destroy();
+                }
+            }
+            _ => {}
+        }
     }
 }
@@ -1,60 +1,48 @@
-use crate::buffers;
-use raw_window_handle::{RawDisplayHandle, RawWindowHandle, WaylandWindowHandle};
+use crate::{buffers, wgpu_state::WgpuState};
+use raw_window_handle::{RawWindowHandle, WaylandWindowHandle};
 use std::ptr::NonNull;
 use wayland_client::{protocol::wl_surface, Proxy};
 
+/// The part of the GPU state that belongs to a single output
 pub struct WgpuSurface {
     pub surface: wgpu::Surface<'static>,
-    pub render_pipeline: wgpu::RenderPipeline,
-    config: wgpu::SurfaceConfiguration,
-    adapter: wgpu::Adapter,
-    shader: wgpu::ShaderModule,
-    pub queue: wgpu::Queue,
-    pub device: wgpu::Device,
-    pub index_buffer: buffers::IndexBuffer,
+    pub config: wgpu::SurfaceConfiguration,
     pub projection_uniform: buffers::ProjectionUniform,
+    pub instance_buffer: buffers::InstanceBuffer,
 }
 
 impl WgpuSurface {
-    pub fn new(
-        surface: &wl_surface::WlSurface,
-        raw_display_handle: RawDisplayHandle,
-        instance: &wgpu::Instance,
-    ) -> Self {
+    pub fn new(surface: &wl_surface::WlSurface, wgpu: &mut WgpuState) -> Self {
         let raw_window_handle = RawWindowHandle::Wayland(WaylandWindowHandle::new(
             NonNull::new(surface.id().as_ptr() as *mut _).unwrap(),
         ));
 
-        let wgpu_surface = unsafe {
-            instance
-                .create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
-                    raw_display_handle,
-                    raw_window_handle,
-                })
-                .unwrap()
-        };
-
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            compatible_surface: Some(&wgpu_surface),
-            ..Default::default()
-        }))
-        .expect("Failed to find suitable adapter");
-
-        let (device, queue) = pollster::block_on(adapter.request_device(&Default::default(), None))
-            .expect("Failed to request device");
+        let mut wgpu_surface =
+            WgpuState::create_surface(&wgpu.instance, wgpu.raw_display_handle, raw_window_handle)
+                .expect("Failed to create surface");
 
-        let projection_uniform = buffers::ProjectionUniform::new(&device, 0.0, 0.0, 0.0, 0.0);
+        // The adapter was picked before there was a surface, on hybrid graphics it may not be the
+        // one the compositor can take buffers from
+        if !wgpu.adapter.is_surface_supported(&wgpu_surface) {
+            log::warn!(
+                "{} can't present to the bar, picking another adapter",
+                wgpu.adapter.get_info().name
+            );
 
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&projection_uniform.bind_group_layout],
-                push_constant_ranges: &[],
-            });
+            drop(wgpu_surface);
+            wgpu.reselect(raw_window_handle);
+            wgpu_surface = WgpuState::create_surface(
+                &wgpu.instance,
+                wgpu.raw_display_handle,
+                raw_window_handle,
+            )
+            .expect("Failed to create surface");
+        }
 
-        let shader = device.create_shader_module(wgpu::include_wgsl!("../../shader.wgsl"));
+        let projection_uniform =
+            buffers::ProjectionUniform::new(&wgpu.renderer.device, 0.0, 0.0, 0.0, 0.0, 1.0);
 
-        let surface_caps = wgpu_surface.get_capabilities(&adapter);
+        let surface_caps = wgpu_surface.get_capabilities(&wgpu.adapter);
         let surface_format = surface_caps
             .formats
             .iter()
@@ -78,63 +66,44 @@ impl WgpuSurface {
             desired_maximum_frame_latency: 2,
         };
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[buffers::Vertex::desc(), buffers::Instance::desc()],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            depth_stencil: None,
-            multiview: None,
-            cache: None,
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-        });
-
-        let indices: &[u16] = &[0, 1, 3, 1, 2, 3];
-        let index_buffer = buffers::IndexBuffer::new(&device, indices);
+        wgpu.renderer.prepare_pipeline(config.format);
 
         Self {
             surface: wgpu_surface,
             config,
-            render_pipeline,
-            adapter,
-            shader,
-            queue,
-            device,
-            index_buffer,
             projection_uniform,
+            // Grows on the first frame if a bar needs more
+            instance_buffer: buffers::InstanceBuffer::new(&wgpu.renderer.device, 256),
         }
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
-        self.config.width = width;
-        self.config.height = height;
-        self.surface.configure(&self.device, &self.config);
+    /// Gets the texture for the next frame. An outdated or lost surface is reconfigured and tried
+    /// once more, as that's what a mode change or resume from suspend leaves behind.
+    pub fn acquire(
+        &self,
+        device: &wgpu::Device,
+    ) -> Result<wgpu::SurfaceTexture, wgpu::SurfaceError> {
+        match self.surface.get_current_texture() {
+            Err(err @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+                log::warn!("{err}, reconfiguring surface");
+                self.reconfigure(device);
+                self.surface.get_current_texture()
+            }
+            result => result,
+        }
+    }
+
+    pub fn reconfigure(&self, device: &wgpu::Device) {
+        self.surface.configure(device, &self.config);
+    }
+
+    /// Sizes the swapchain in physical pixels for a `width` x `height` logical surface, drawing
+    /// stays in logical coordinates
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32, scale: f32) {
+        self.config.width = ((width as f32 * scale).round() as u32).max(1);
+        self.config.height = ((height as f32 * scale).round() as u32).max(1);
+        self.reconfigure(device);
+        self.projection_uniform =
+            buffers::ProjectionUniform::new(device, 0.0, width as f32, 0.0, height as f32, scale);
     }
 }
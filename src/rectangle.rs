@@ -1,18 +1,18 @@
-mod border;
+pub mod border;
 mod filter;
-mod image;
-mod outline;
+pub mod image;
+pub mod outline;
 mod transform;
 
 use crate::buffers;
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum BoxSizing {
     ContentBox,
     BorderBox,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 struct BoxShadow {
     x_offset: f32,
     y_offset: f32,
@@ -21,7 +21,7 @@ struct BoxShadow {
     inset: bool,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct PaddingSize {
     pub top: f32,
     pub right: f32,
@@ -43,18 +43,20 @@ impl PaddingSize {
 // width             | [x]                   | [x]
 // height            | [x]                   | [x]
 // bg-color          | [x]                   | [x]
-// bg-image          | [ ]                   | [ ]
+// bg-image          | [x]                   | [x]
 // box-sizing        | [x]                   | [x]
 // padding           | [x]                   | [x]
 // border            | [x]                   | [x]
-// box-shadow        | [x]                   | [ ]
+// box-shadow        | [x]                   | [x]
 // outline           | [x]                   | [x]
+#[derive(Clone)]
 pub struct Rectangle {
     x: f32,
     y: f32,
     width: f32,
     height: f32,
     background_color: [f32; 4],
+    background_image: Option<image::Image>,
     padding: PaddingSize,
     box_sizing: BoxSizing,
     border: border::Border,
@@ -104,6 +106,12 @@ impl Rectangle {
         self
     }
 
+    /// Draws the shadow inside the padding box instead of around the rectangle
+    pub fn set_boxshadow_inset(mut self, inset: bool) -> Self {
+        self.box_shadow.inset = inset;
+        self
+    }
+
     pub fn set_size(mut self, width: f32, height: f32) -> Self {
         self.width = width;
         self.height = height;
@@ -132,18 +140,12 @@ impl Rectangle {
 
     // Getter for extents
     pub fn get_extents(&self) -> Extents {
+        let border = self.border.used_size();
+
         let (width, height) = match self.box_sizing {
             BoxSizing::ContentBox => (
-                self.width
-                    + self.padding.left
-                    + self.padding.right
-                    + self.border.size.left
-                    + self.border.size.right,
-                self.height
-                    + self.padding.top
-                    + self.padding.bottom
-                    + self.border.size.top
-                    + self.border.size.bottom,
+                self.width + self.padding.left + self.padding.right + border.left + border.right,
+                self.height + self.padding.top + self.padding.bottom + border.top + border.bottom,
             ),
             BoxSizing::BorderBox => (self.width, self.height),
         };
@@ -156,29 +158,99 @@ impl Rectangle {
         }
     }
 
+    /// Area children are laid out in, i.e. the extents without border and padding
+    pub fn get_content_extents(&self) -> Extents {
+        let extents = self.get_extents();
+        let border = self.border.used_size();
+
+        let left = border.left + self.padding.left;
+        let top = border.top + self.padding.top;
+        let horizontal = left + border.right + self.padding.right;
+        let vertical = top + border.bottom + self.padding.bottom;
+
+        Extents {
+            x: extents.x + left,
+            y: extents.y + top,
+            width: (extents.width - horizontal).max(0.0),
+            height: (extents.height - vertical).max(0.0),
+        }
+    }
+
+    /// Sets the size so that `get_extents` reports `width` x `height`, regardless of box sizing
+    pub fn set_extents_size(self, width: f32, height: f32) -> Self {
+        match self.box_sizing {
+            BoxSizing::ContentBox => {
+                let border = self.border.used_size();
+                let horizontal =
+                    border.left + border.right + self.padding.left + self.padding.right;
+                let vertical = border.top + border.bottom + self.padding.top + self.padding.bottom;
+
+                self.set_size((width - horizontal).max(0.0), (height - vertical).max(0.0))
+            }
+            BoxSizing::BorderBox => self.set_size(width, height),
+        }
+    }
+
+    pub fn get_background_image(&self) -> Option<&image::Image> {
+        self.background_image.as_ref()
+    }
+
     pub fn get_instance(&self) -> buffers::Instance {
         let extents = self.get_extents();
+        let border = self.border.used_size();
+
+        // The offset only matters when there's an outline to push away
+        let outline = match self.outline.used_width() {
+            width if width > 0.0 => [width, self.outline.offset],
+            _ => [0.0, 0.0],
+        };
+        let outline_reach = outline[0] + outline[1];
+
+        // The blur is a gaussian with a standard deviation of half the softness, so it fades out
+        // over three of them past the shadow's edge. Inset shadows stay inside the rectangle
+        let shadow = if self.box_shadow.inset || self.box_shadow.color[3] <= 0.0 {
+            0.0
+        } else {
+            self.box_shadow
+                .x_offset
+                .abs()
+                .max(self.box_shadow.y_offset.abs())
+                + self.box_shadow.softness * 1.5
+        };
 
-        let x = extents.x - self.outline.width - self.outline.offset;
+        let reach = outline_reach.max(shadow);
 
-        let y = extents.y - self.outline.width - self.outline.offset;
+        let x = extents.x - reach;
 
-        let width = extents.width + (self.outline.width + self.outline.offset) * 2.0;
+        let y = extents.y - reach;
 
-        let height = extents.height + (self.outline.width + self.outline.offset) * 2.0;
+        let width = extents.width + reach * 2.0;
 
-        // TODO: calculate size of shadow and get max of either outline width + outline.offset or
-        // the calculated shadow (cant just take offset as blurring kind makes it different size)
+        let height = extents.height + reach * 2.0;
 
-        let c = self.background_color;
+        // The image covers the padding box, the instance also spans border and outline
+        let (kind, image) = match self.background_image.as_ref() {
+            Some(image) => (
+                buffers::InstanceKind::Image,
+                image.placement(
+                    extents.width - border.left - border.right,
+                    extents.height - border.top - border.bottom,
+                ),
+            ),
+            None => (
+                buffers::InstanceKind::Rectangle,
+                image::Placement::default(),
+            ),
+        };
 
         buffers::Instance {
             dimensions: [x, y, width, height],
-            color: [c[0] * c[3], c[1] * c[3], c[2] * c[3], c[3]], // Premultiply colors
+            // Straight alpha, the shader composites the layers and the pipeline blends it
+            color: self.background_color,
             border_radius: self.border.radius.to_array(),
-            border_size: self.border.size.to_array(),
-            border_color: self.border.color,
-            outline: [self.outline.width, self.outline.offset],
+            border_size: border.to_array(),
+            border_color: self.border.color.to_packed(),
+            outline,
             outline_color: self.outline.color,
             filter: [self.brightness, self.saturate, self.contrast, self.invert],
             grayscale: self.grayscale,
@@ -186,6 +258,28 @@ impl Rectangle {
             rotation: self.rotate,
             translate: self.translate,
             skew: self.skew,
+            image_spacing: image.spacing,
+            // Filled in from the image atlas when the instances are collected
+            texture: [0.0; 4],
+            image: [
+                image.offset[0],
+                image.offset[1],
+                image.size[0],
+                image.size[1],
+            ],
+            shadow: [
+                self.box_shadow.x_offset,
+                self.box_shadow.y_offset,
+                self.box_shadow.softness,
+                reach - outline_reach,
+            ],
+            shadow_color: self.box_shadow.color,
+            flags: [
+                kind as u32,
+                image.repeat[0] | image.repeat[1] << 1,
+                self.border.style as u32 | (self.outline.style.to_border_style() as u32) << 8,
+                self.box_shadow.inset as u32,
+            ],
         }
     }
 }
@@ -200,6 +294,7 @@ impl Default for Rectangle {
             height: 1.0,
             padding: PaddingSize::default(),
             background_color: [0.0, 0.0, 0.0, 0.0],
+            background_image: None,
             border: border::Border::default(),
             outline: outline::Outline::default(),
             box_sizing: BoxSizing::ContentBox,
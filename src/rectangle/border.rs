@@ -1,7 +1,7 @@
 use super::Rectangle;
 
-// TODO: Implement BorderStyle and make it available to set color per border
-
+/// Follows CSS. The discriminants are what the shader sees
+#[derive(Clone, Copy, PartialEq)]
 pub enum BorderStyle {
     None,
     Solid,
@@ -15,7 +15,7 @@ pub enum BorderStyle {
     Hidden,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct BorderRadius {
     top_left: f32,
     top_right: f32,
@@ -24,17 +24,18 @@ pub struct BorderRadius {
 }
 
 impl BorderRadius {
+    /// Clockwise from the top left, like the shader expects
     pub fn to_array(&self) -> [f32; 4] {
         [
             self.top_left,
             self.top_right,
-            self.bottom_left,
             self.bottom_right,
+            self.bottom_left,
         ]
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct BorderSize {
     pub top: f32,
     pub right: f32,
@@ -48,18 +49,46 @@ impl BorderSize {
     }
 }
 
+#[derive(Default, Clone)]
+pub struct BorderColor {
+    pub top: [f32; 4],
+    pub right: [f32; 4],
+    pub bottom: [f32; 4],
+    pub left: [f32; 4],
+}
+
+impl BorderColor {
+    /// Each side as 8 bit RGBA, to fit all four in a single vertex attribute
+    pub fn to_packed(&self) -> [u32; 4] {
+        [self.top, self.right, self.bottom, self.left].map(|color| {
+            u32::from_le_bytes(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct Border {
     pub radius: BorderRadius,
     pub size: BorderSize,
-    pub color: [f32; 4],
+    pub color: BorderColor,
     pub style: BorderStyle,
 }
 
+impl Border {
+    /// Like CSS, `None` and `Hidden` borders take up no space whatever their size
+    pub fn used_size(&self) -> BorderSize {
+        match self.style {
+            BorderStyle::None | BorderStyle::Hidden => BorderSize::default(),
+            _ => self.size,
+        }
+    }
+}
+
 impl Default for Border {
     fn default() -> Self {
         Self {
             radius: BorderRadius::default(),
-            color: [0.0, 0.0, 0.0, 0.0],
+            color: BorderColor::default(),
             size: BorderSize::default(),
             style: BorderStyle::Solid,
         }
@@ -77,8 +106,31 @@ impl Rectangle {
         self
     }
 
+    /// Sets the color of all four sides
     pub fn set_border_color(mut self, r: f32, g: f32, b: f32, a: f32) -> Self {
-        self.border.color = [r, g, b, a];
+        let color = [r, g, b, a];
+        self.border.color = BorderColor {
+            top: color,
+            right: color,
+            bottom: color,
+            left: color,
+        };
+        self
+    }
+
+    pub fn set_border_colors(
+        mut self,
+        top: [f32; 4],
+        right: [f32; 4],
+        bottom: [f32; 4],
+        left: [f32; 4],
+    ) -> Self {
+        self.border.color = BorderColor {
+            top,
+            right,
+            bottom,
+            left,
+        };
         self
     }
 
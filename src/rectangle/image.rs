@@ -1,4 +1,11 @@
-use std::rc::Rc;
+use super::Rectangle;
+use crate::atlas::Atlas;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 //background-attachment: Specifies if the background image scrolls with the page or is fixed (e.g., scroll, fixed, local).
 //
@@ -8,13 +15,15 @@ use std::rc::Rc;
 //
 //background-blend-mode: Specifies how the background image should blend with the background color or other backgrounds (e.g., multiply, screen, overlay).
 
-enum ImageSize {
+#[derive(Clone, Copy)]
+pub enum ImageSize {
     Cover,
     Contain,
     Dimensions(u32, u32),
 }
 
-enum ImageRepeat {
+#[derive(Clone, Copy)]
+pub enum ImageRepeat {
     Repeat,
     NoRepeat,
     RepeatX,
@@ -23,10 +32,355 @@ enum ImageRepeat {
     Round,
 }
 
+enum Source {
+    /// Straight alpha RGBA pixels
+    Pixels(Vec<u8>),
+    /// Rasterized at the size it's drawn at, so it stays sharp when enlarged or on HiDPI outputs
+    Svg(Box<resvg::usvg::Tree>),
+}
+
+pub struct Decoded {
+    id: usize,
+    width: u32,
+    height: u32,
+    source: Source,
+}
+
+#[derive(Clone)]
 pub struct Image {
-    path: Option<Rc<std::path::Path>>, // This probably should be an actual image or idk yet, maybe
-    // a pointer to a texture atlas
+    data: Rc<Decoded>,
     size: ImageSize,
-    position: (u32, u32),
+    position: (f32, f32),
+    repeat: ImageRepeat,
+}
+
+/// Where a single tile of the image lands, relative to the padding box
+#[derive(Default)]
+pub struct Placement {
+    pub offset: [f32; 2],
+    pub size: [f32; 2],
+    pub spacing: [f32; 2],
+    pub repeat: [u32; 2],
+}
+
+thread_local! {
+    // Decoded once per path, failures included so a broken file is only reported once
+    static IMAGES: RefCell<HashMap<PathBuf, Option<Rc<Decoded>>>> = RefCell::default();
+}
+
+fn load(path: &Path) -> Option<Rc<Decoded>> {
+    if let Some(image) = IMAGES.with_borrow(|images| images.get(path).cloned()) {
+        return image;
+    }
+
+    let decoded = decode(path)
+        .inspect_err(|err| log::error!("failed to load image {}: {err}", path.display()))
+        .ok()
+        .map(|(width, height, source)| {
+            Rc::new(Decoded {
+                id: IMAGES.with_borrow(|images| images.len()),
+                width,
+                height,
+                source,
+            })
+        });
+
+    IMAGES.with_borrow_mut(|images| images.insert(path.to_path_buf(), decoded.clone()));
+
+    decoded
+}
+
+fn decode(path: &Path) -> Result<(u32, u32, Source), String> {
+    let is_svg = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+
+    if !is_svg {
+        let image = image::open(path).map_err(|err| err.to_string())?.to_rgba8();
+        return Ok((
+            image.width(),
+            image.height(),
+            Source::Pixels(image.into_raw()),
+        ));
+    }
+
+    let data = std::fs::read(path).map_err(|err| err.to_string())?;
+    let tree = resvg::usvg::Tree::from_data(&data, &resvg::usvg::Options::default())
+        .map_err(|err| err.to_string())?;
+
+    let size = tree.size().to_int_size();
+    Ok((size.width(), size.height(), Source::Svg(Box::new(tree))))
+}
+
+/// Renders `tree` stretched to `width` x `height`, as straight alpha RGBA pixels
+fn rasterize(tree: &resvg::usvg::Tree, width: u32, height: u32) -> Vec<u8> {
+    let mut pixmap =
+        resvg::tiny_skia::Pixmap::new(width, height).expect("Failed to create the svg pixmap");
+    let transform = resvg::tiny_skia::Transform::from_scale(
+        width as f32 / tree.size().width(),
+        height as f32 / tree.size().height(),
+    );
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+
+    // tiny-skia renders premultiplied pixels, the atlas holds straight alpha
+    pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let pixel = pixel.demultiply();
+            [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+        })
+        .collect()
+}
+
+impl Image {
+    /// Pixels the image is uploaded at for tiles drawn `size` physical pixels big. Only SVGs
+    /// follow the tile, other images are scaled by the sampler.
+    fn pixel_size(&self, size: [f32; 2]) -> (u32, u32) {
+        match self.data.source {
+            Source::Pixels(_) => (self.data.width, self.data.height),
+            Source::Svg(_) => (
+                size[0].ceil().max(1.0) as u32,
+                size[1].ceil().max(1.0) as u32,
+            ),
+        }
+    }
+
+    pub fn placement(&self, width: f32, height: f32) -> Placement {
+        let (image_width, image_height) = (self.data.width as f32, self.data.height as f32);
+
+        let (tile_width, tile_height) = match self.size {
+            ImageSize::Cover => {
+                let scale = (width / image_width).max(height / image_height);
+                (image_width * scale, image_height * scale)
+            }
+            ImageSize::Contain => {
+                let scale = (width / image_width).min(height / image_height);
+                (image_width * scale, image_height * scale)
+            }
+            ImageSize::Dimensions(width, height) => (width as f32, height as f32),
+        };
+
+        let (repeat_x, repeat_y) = match self.repeat {
+            ImageRepeat::NoRepeat => (false, false),
+            ImageRepeat::RepeatX => (true, false),
+            ImageRepeat::RepeatY => (false, true),
+            ImageRepeat::Repeat | ImageRepeat::Space | ImageRepeat::Round => (true, true),
+        };
+
+        let x = axis(self.repeat, width, tile_width, self.position.0, repeat_x);
+        let y = axis(self.repeat, height, tile_height, self.position.1, repeat_y);
+
+        Placement {
+            offset: [x.0, y.0],
+            size: [x.1, y.1],
+            spacing: [x.2, y.2],
+            repeat: [repeat_x as u32, repeat_y as u32],
+        }
+    }
+}
+
+// Offset, tile size and spacing along one axis of a box `available` long
+fn axis(
     repeat: ImageRepeat,
+    available: f32,
+    size: f32,
+    position: f32,
+    repeats: bool,
+) -> (f32, f32, f32) {
+    if !repeats || size <= 0.0 {
+        return (position, size, 0.0);
+    }
+
+    match repeat {
+        // As many whole tiles as fit, with the leftover space spread between them
+        ImageRepeat::Space => {
+            let count = (available / size).floor();
+            if count < 2.0 {
+                return (position, size, 0.0);
+            }
+
+            (0.0, size, (available - count * size) / (count - 1.0))
+        }
+        // Tiles are rescaled so a whole number of them fits
+        ImageRepeat::Round => {
+            let count = (available / size).round().max(1.0);
+            (position, available / count, 0.0)
+        }
+        _ => (position, size, 0.0),
+    }
+}
+
+/// Every background image drawn so far, uploaded on first use
+pub struct ImageAtlas {
+    atlas: Atlas,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    // Keyed by image id and the size it was uploaded at. Images too big for the atlas to ever
+    // hold are cached as `None` so they aren't retried every frame, others grow it instead
+    images: HashMap<(usize, u32, u32), Option<[f32; 4]>>,
+    full: bool,
+    /// Bumped whenever cached coordinates stop being valid, see `make_room`
+    generation: u64,
+}
+
+impl ImageAtlas {
+    pub fn new(device: &wgpu::Device) -> Self {
+        // Images are stored sRGB encoded, so sampling yields linear colors like everything else
+        let atlas = Atlas::new(device, wgpu::TextureFormat::Rgba8UnormSrgb, 2048);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Image sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("image atlas bind group layout"),
+        });
+
+        let bind_group = atlas.bind_group(
+            device,
+            &bind_group_layout,
+            &sampler,
+            "image atlas bind group",
+        );
+
+        Self {
+            atlas,
+            bind_group_layout,
+            bind_group,
+            sampler,
+            images: HashMap::new(),
+            full: false,
+            generation: 0,
+        }
+    }
+
+    /// `size` is how big a tile of the image is drawn, in physical pixels
+    pub fn get(&mut self, queue: &wgpu::Queue, image: &Image, size: [f32; 2]) -> Option<[f32; 4]> {
+        let data = &image.data;
+        let (width, height) = image.pixel_size(size);
+        let key = (data.id, width, height);
+
+        if let Some(uv) = self.images.get(&key) {
+            return *uv;
+        }
+
+        if !self.atlas.could_fit(width, height) {
+            log::error!("image of {width}x{height} doesn't fit in the image atlas");
+            self.images.insert(key, None);
+            return None;
+        }
+
+        let Some(region) = self.atlas.allocate(width, height) else {
+            self.full = true;
+            return None;
+        };
+
+        match &data.source {
+            Source::Pixels(pixels) => self.atlas.upload(queue, region, pixels),
+            Source::Svg(tree) => self
+                .atlas
+                .upload(queue, region, &rasterize(tree, width, height)),
+        }
+
+        let uv = self.atlas.uv(region);
+        self.images.insert(key, Some(uv));
+
+        Some(uv)
+    }
+
+    /// Whether an image didn't fit since the last `make_room`, meaning it wasn't drawn
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Grows the atlas after an image didn't fit, or empties it once it can't grow any more.
+    /// Either way everything in it has to be added again, by every tree drawn with it.
+    pub fn make_room(&mut self, device: &wgpu::Device) {
+        if !self.full {
+            return;
+        }
+
+        if self.atlas.grow(device) {
+            self.bind_group = self.atlas.bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.sampler,
+                "image atlas bind group",
+            );
+        } else {
+            self.atlas.clear();
+        }
+
+        self.images.clear();
+        self.full = false;
+        self.generation += 1;
+    }
+}
+
+impl Rectangle {
+    /// Decodes a PNG, JPEG or SVG file to draw over the background color. Files are decoded once
+    /// and shared between rectangles, a file that fails to decode is logged and not drawn.
+    pub fn set_background_image<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.background_image = load(path.as_ref()).map(|data| Image {
+            data,
+            size: ImageSize::Dimensions(0, 0),
+            position: (0.0, 0.0),
+            repeat: ImageRepeat::Repeat,
+        });
+
+        // Like CSS, images keep their intrinsic size unless told otherwise
+        if let Some(image) = self.background_image.as_mut() {
+            image.size = ImageSize::Dimensions(image.data.width, image.data.height);
+        }
+
+        self
+    }
+
+    pub fn set_background_size(mut self, size: ImageSize) -> Self {
+        if let Some(image) = self.background_image.as_mut() {
+            image.size = size;
+        }
+        self
+    }
+
+    pub fn set_background_position(mut self, x: f32, y: f32) -> Self {
+        if let Some(image) = self.background_image.as_mut() {
+            image.position = (x, y);
+        }
+        self
+    }
+
+    pub fn set_background_repeat(mut self, repeat: ImageRepeat) -> Self {
+        if let Some(image) = self.background_image.as_mut() {
+            image.repeat = repeat;
+        }
+        self
+    }
 }
@@ -1,7 +1,6 @@
-use super::Rectangle;
-
-// TODO: Implement OutlineStyle
+use super::{border::BorderStyle, Rectangle};
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum OutlineStyle {
     None,
     Solid,
@@ -13,6 +12,7 @@ pub enum OutlineStyle {
     Hidden,
 }
 
+#[derive(Clone)]
 pub struct Outline {
     pub width: f32,
     pub color: [f32; 4],
@@ -20,6 +20,32 @@ pub struct Outline {
     pub offset: f32,
 }
 
+impl OutlineStyle {
+    /// Outlines are drawn like a border of the same style
+    pub fn to_border_style(self) -> BorderStyle {
+        match self {
+            OutlineStyle::None => BorderStyle::None,
+            OutlineStyle::Solid => BorderStyle::Solid,
+            OutlineStyle::Dotted => BorderStyle::Dotted,
+            OutlineStyle::Dashed => BorderStyle::Dashed,
+            OutlineStyle::Double => BorderStyle::Double,
+            OutlineStyle::Groove => BorderStyle::Groove,
+            OutlineStyle::Ridge => BorderStyle::Ridge,
+            OutlineStyle::Hidden => BorderStyle::Hidden,
+        }
+    }
+}
+
+impl Outline {
+    /// `None` and `Hidden` outlines aren't drawn, so they take up no room around the rectangle
+    pub fn used_width(&self) -> f32 {
+        match self.style {
+            OutlineStyle::None | OutlineStyle::Hidden => 0.0,
+            _ => self.width,
+        }
+    }
+}
+
 impl Default for Outline {
     fn default() -> Self {
         Self {
@@ -0,0 +1,287 @@
+use crate::{buffers, rectangle, text, tree};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Vulkan first, as it's usually the faster of the two, then GL for machines without it
+pub const AUTO: &[wgpu::Backends] = &[wgpu::Backends::VULKAN, wgpu::Backends::GL];
+
+/// Backends this build can draw with, see the `vulkan` and `gl` features. Those only rule a
+/// backend out here, wgpu is built with both either way.
+pub fn available_backends() -> wgpu::Backends {
+    let mut backends = wgpu::Backends::empty();
+    backends.set(wgpu::Backends::VULKAN, cfg!(feature = "vulkan"));
+    backends.set(wgpu::Backends::GL, cfg!(feature = "gl"));
+    backends
+}
+
+/// Creates a surface with an instance, to find adapters that can present to it
+pub type CreateSurface = dyn Fn(&wgpu::Instance) -> Option<wgpu::Surface<'static>>;
+
+/// Tries each entry of `order` in turn, so an earlier backend is picked even when a later one has
+/// a more capable adapter. Backends missing from the build are skipped. With `surface`, only
+/// adapters that can present to the surface it creates for a backend's instance are considered.
+pub fn request_adapter(
+    order: &[wgpu::Backends],
+    options: &wgpu::RequestAdapterOptions,
+    surface: Option<&CreateSurface>,
+) -> Option<(wgpu::Instance, wgpu::Adapter)> {
+    let enabled = available_backends();
+    if order
+        .iter()
+        .all(|&backends| (backends & enabled).is_empty())
+    {
+        log::error!("none of {order:?} is enabled in this build");
+        return None;
+    }
+
+    order.iter().find_map(|&backends| {
+        let backends = backends & enabled;
+        if backends.is_empty() {
+            return None;
+        }
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+
+        let compatible_surface = match surface {
+            Some(create) => Some(create(&instance)?),
+            None => None,
+        };
+
+        // An adapter that can't present is no better than none, the next backend may have one
+        let Some(adapter) =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: options.power_preference,
+                force_fallback_adapter: options.force_fallback_adapter,
+                compatible_surface: compatible_surface.as_ref(),
+            }))
+            .filter(|adapter| {
+                compatible_surface
+                    .as_ref()
+                    .is_none_or(|surface| adapter.is_surface_supported(surface))
+            })
+        else {
+            log::info!("no suitable adapter on {backends:?}, trying the next backend");
+            return None;
+        };
+
+        let info = adapter.get_info();
+        log::info!("drawing with {} on {:?}", info.name, info.backend);
+
+        Some((instance, adapter))
+    })
+}
+
+/// Asks for what downlevel devices offer, which `Limits::default()` is more than for many GL
+/// drivers, but with textures as big as `adapter` supports so the atlases can keep growing
+pub fn request_device(
+    adapter: &wgpu::Adapter,
+) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+    let descriptor = wgpu::DeviceDescriptor {
+        required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        ..Default::default()
+    };
+
+    pollster::block_on(adapter.request_device(&descriptor, None))
+}
+
+/// Everything on the GPU needed to draw trees that doesn't belong to a single target, shared by
+/// all of them
+pub struct Renderer {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    /// The unit quad every instance is drawn with
+    pub vertex_buffer: buffers::VertexBuffer,
+    pub index_buffer: buffers::IndexBuffer,
+    pub glyph_atlas: text::atlas::GlyphAtlas,
+    pub image_atlas: rectangle::image::ImageAtlas,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    // Targets almost always agree on a format, so there's usually just the one
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+    // Set from wgpu's callback, which may run on any thread
+    lost: Arc<AtomicBool>,
+}
+
+impl Renderer {
+    pub fn new(device: wgpu::Device, queue: wgpu::Queue) -> Self {
+        let lost = Arc::new(AtomicBool::new(false));
+        let flag = lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Also called when the device is dropped or the callback replaced, which is expected
+            if matches!(
+                reason,
+                wgpu::DeviceLostReason::Unknown | wgpu::DeviceLostReason::Destroyed
+            ) {
+                log::error!("device lost: {message}");
+                flag.store(true, Ordering::Relaxed);
+            }
+        });
+
+        // wgpu panics on errors by default, which is kept in debug builds so mistakes are found.
+        // Otherwise a broken frame is better than no bar at all.
+        if !cfg!(debug_assertions) {
+            device.on_uncaptured_error(Box::new(|err| log::error!("wgpu: {err}")));
+        }
+
+        let glyph_atlas = text::atlas::GlyphAtlas::new(&device);
+        let image_atlas = rectangle::image::ImageAtlas::new(&device);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[
+                &buffers::ProjectionUniform::create_bind_group_layout(&device),
+                &glyph_atlas.bind_group_layout,
+                &image_atlas.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+
+        let vertices = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]
+            .map(|position| buffers::Vertex { position });
+        let vertex_buffer = buffers::VertexBuffer::new(&device, &vertices);
+
+        let indices: &[u16] = &[0, 1, 3, 1, 2, 3];
+        let index_buffer = buffers::IndexBuffer::new(&device, indices);
+
+        Self {
+            device,
+            queue,
+            vertex_buffer,
+            index_buffer,
+            glyph_atlas,
+            image_atlas,
+            shader,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+            lost,
+        }
+    }
+
+    /// Whether the device stopped working, after which nothing drawn with it shows up and it has
+    /// to be replaced
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// Builds the pipeline drawing to `format`, unless an earlier target already needed it
+    pub fn prepare_pipeline(&mut self, format: wgpu::TextureFormat) {
+        if self.pipelines.contains_key(&format) {
+            return;
+        }
+
+        let render_pipeline = self
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Render Pipeline"),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[buffers::Vertex::desc(), buffers::Instance::desc()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                depth_stencil: None,
+                multiview: None,
+                cache: None,
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+            });
+
+        self.pipelines.insert(format, render_pipeline);
+    }
+
+    /// Whether `tree` changed since it was last prepared, or the atlases it was prepared with did
+    pub fn needs_prepare(&self, tree: &tree::Tree) -> bool {
+        tree.is_dirty()
+            || tree.atlas_generations()
+                != [self.glyph_atlas.generation(), self.image_atlas.generation()]
+    }
+
+    /// See `Tree::prepare`, `scale` is the physical pixels per logical one of the target
+    pub fn prepare(
+        &mut self,
+        tree: &mut tree::Tree,
+        instance_buffer: &mut buffers::InstanceBuffer,
+        scale: f32,
+    ) -> bool {
+        tree.prepare(
+            &self.device,
+            &self.queue,
+            instance_buffer,
+            &mut self.glyph_atlas,
+            &mut self.image_atlas,
+            scale,
+        )
+    }
+
+    /// Records clearing `view` and drawing what the last `prepare` wrote to `instance_buffer`.
+    /// Panics unless `prepare_pipeline` was called for `format`.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        projection_uniform: &buffers::ProjectionUniform,
+        tree: &tree::Tree,
+        instance_buffer: &buffers::InstanceBuffer,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.pipelines[&format]);
+        render_pass.set_bind_group(0, &projection_uniform.bind_group, &[]);
+
+        tree.render(
+            &mut render_pass,
+            &self.vertex_buffer,
+            &self.index_buffer,
+            instance_buffer,
+            &self.glyph_atlas,
+            &self.image_atlas,
+        );
+    }
+}
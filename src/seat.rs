@@ -1,32 +1,169 @@
+mod keyboard;
+mod touch;
+
+use calloop::LoopHandle;
 use wayland_client::{
-    protocol::{wl_pointer, wl_seat},
-    Connection, Dispatch, QueueHandle, WEnum,
+    protocol::{wl_pointer, wl_seat, wl_surface},
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
 };
 
-use crate::StatusBar;
+use crate::{input, output, tree, StatusBar};
 
 pub struct Pointer {
     pointer: Option<wl_pointer::WlPointer>,
-    pub x: i64,
-    pub y: i64,
+    /// Surface the pointer is over, between `Enter` and `Leave`
+    surface: Option<wl_surface::WlSurface>,
+    pub x: f64,
+    pub y: f64,
+    scroll: Scroll,
+}
+
+/// Distance smooth scrolling has to cover for a step, about what a wheel click scrolls
+const SCROLL_STEP: f64 = 15.0;
+
+/// Axis events since the last frame, and partial steps left over from earlier ones. Indexed by
+/// axis, horizontal then vertical.
+#[derive(Default)]
+struct Scroll {
+    source: Option<input::ScrollSource>,
+    delta: [f64; 2],
+    /// Since version 8, in fractions of 120 per wheel click so high resolution wheels can send
+    /// less than a click
+    value120: [i32; 2],
+    /// Versions 5 to 7, in wheel clicks
+    discrete: [i32; 2],
+    remainder120: [i32; 2],
+    remainder: [f64; 2],
+}
+
+impl Scroll {
+    /// Turns what was collected into steps and a distance, `None` if it didn't scroll at all
+    fn take(&mut self) -> Option<(input::ScrollSource, [i32; 2], [f32; 2])> {
+        let source = self.source.take().unwrap_or(input::ScrollSource::Wheel);
+        let mut steps = [0; 2];
+
+        for axis in 0..2 {
+            // Turning back starts over, what was left over points the other way
+            if self.value120[axis].signum() * self.remainder120[axis].signum() < 0 {
+                self.remainder120[axis] = 0;
+            }
+            if self.delta[axis] * self.remainder[axis] < 0.0 {
+                self.remainder[axis] = 0.0;
+            }
+
+            steps[axis] = if self.value120[axis] != 0 {
+                self.remainder120[axis] += self.value120[axis];
+                let steps = self.remainder120[axis] / 120;
+                self.remainder120[axis] %= 120;
+                steps
+            } else if self.discrete[axis] != 0 {
+                self.discrete[axis]
+            } else {
+                self.remainder[axis] += self.delta[axis];
+                let steps = (self.remainder[axis] / SCROLL_STEP).trunc();
+                self.remainder[axis] -= steps * SCROLL_STEP;
+                steps as i32
+            };
+        }
+
+        let delta = self.delta.map(|delta| delta as f32);
+        self.delta = [0.0; 2];
+        self.value120 = [0; 2];
+        self.discrete = [0; 2];
+
+        (steps != [0; 2] || delta != [0.0; 2]).then_some((source, steps, delta))
+    }
+
+    /// Forgets partial steps, e.g. when the fingers were lifted off the touchpad
+    fn stop(&mut self, axis: usize) {
+        self.remainder120[axis] = 0;
+        self.remainder[axis] = 0.0;
+    }
+}
+
+fn axis_index(axis: wl_pointer::Axis) -> usize {
+    match axis {
+        wl_pointer::Axis::HorizontalScroll => 0,
+        _ => 1,
+    }
 }
 
 pub struct Seat {
+    /// Registry name, to find the seat again when it's removed
+    pub id: u32,
     pub seat: wl_seat::WlSeat,
     pub pointer: Option<Pointer>,
+    touch: Option<touch::Touch>,
+    keyboard: Option<keyboard::Keyboard>,
+    /// Kept up to date by the keyboard, pointer events don't carry them
+    modifiers: input::Modifiers,
+}
+
+impl Seat {
+    pub fn new(id: u32, seat: wl_seat::WlSeat) -> Self {
+        Self {
+            id,
+            seat,
+            pointer: None,
+            touch: None,
+            keyboard: None,
+            modifiers: input::Modifiers::default(),
+        }
+    }
+
+    /// Releases the seat and its devices, once the seat is gone
+    pub fn release(self, loop_handle: &LoopHandle<'static, StatusBar>) {
+        if let Some(pointer) = self.pointer {
+            pointer.release();
+        }
+
+        if let Some(touch) = self.touch {
+            touch.release(loop_handle);
+        }
+
+        if let Some(keyboard) = self.keyboard {
+            keyboard.release();
+        }
+
+        if self.seat.version() >= 5 {
+            self.seat.release();
+        }
+    }
+}
+
+impl Pointer {
+    fn release(self) {
+        if let Some(pointer) = self.pointer.filter(|pointer| pointer.version() >= 3) {
+            pointer.release();
+        }
+    }
+}
+
+/// Tree of the bar drawn on `surface`, if it's still around
+fn tree_mut<'a>(
+    outputs: &'a mut [output::Output],
+    surface: &wl_surface::WlSurface,
+) -> Option<&'a mut tree::Tree> {
+    outputs
+        .iter_mut()
+        .filter_map(|output| output.surface.as_mut())
+        .find(|bar| bar.surface == *surface)
+        .map(|bar| &mut bar.background)
 }
 
 impl Dispatch<wl_seat::WlSeat, ()> for StatusBar {
     fn event(
         state: &mut Self,
-        _proxy: &wl_seat::WlSeat,
+        proxy: &wl_seat::WlSeat,
         event: wl_seat::Event,
         _data: &(),
         _conn: &Connection,
         qh: &QueueHandle<Self>,
     ) {
-        // Can't be called if seat wasn't created
-        let seat = state.seat.as_mut().unwrap();
+        // Events may still be in flight for a seat that was removed
+        let Some(seat) = state.seats.iter_mut().find(|seat| seat.seat == *proxy) else {
+            return;
+        };
 
         let wl_seat::Event::Capabilities {
             capabilities: WEnum::Value(capabilities),
@@ -35,12 +172,39 @@ impl Dispatch<wl_seat::WlSeat, ()> for StatusBar {
             return;
         };
 
-        if capabilities.contains(wl_seat::Capability::Pointer) {
+        // Capabilities are sent again whenever a device is added or removed
+        let has_pointer = capabilities.contains(wl_seat::Capability::Pointer);
+        if has_pointer && seat.pointer.is_none() {
             seat.pointer = Some(Pointer {
                 pointer: Some(seat.seat.get_pointer(qh, ())),
-                x: 0,
-                y: 0,
+                surface: None,
+                x: 0.0,
+                y: 0.0,
+                scroll: Scroll::default(),
             });
+        } else if !has_pointer {
+            if let Some(pointer) = seat.pointer.take() {
+                pointer.release();
+            }
+        }
+
+        let has_touch = capabilities.contains(wl_seat::Capability::Touch);
+        if has_touch && seat.touch.is_none() {
+            seat.touch = Some(touch::Touch::new(seat.seat.get_touch(qh, ())));
+        } else if !has_touch {
+            if let Some(touch) = seat.touch.take() {
+                touch.release(&state.loop_handle);
+            }
+        }
+
+        let has_keyboard = capabilities.contains(wl_seat::Capability::Keyboard);
+        if has_keyboard && seat.keyboard.is_none() {
+            seat.keyboard = Some(keyboard::Keyboard::new(seat.seat.get_keyboard(qh, ())));
+        } else if !has_keyboard {
+            if let Some(keyboard) = seat.keyboard.take() {
+                keyboard.release();
+            }
+            seat.modifiers = input::Modifiers::default();
         }
     }
 }
@@ -48,34 +212,156 @@ impl Dispatch<wl_seat::WlSeat, ()> for StatusBar {
 impl Dispatch<wl_pointer::WlPointer, ()> for StatusBar {
     fn event(
         state: &mut Self,
-        _proxy: &wl_pointer::WlPointer,
+        proxy: &wl_pointer::WlPointer,
         event: wl_pointer::Event,
         _data: &(),
         _conn: &Connection,
         _qhandle: &QueueHandle<Self>,
     ) {
-        // Can't be called if pointer wasn't created
-        let pointer = state.seat.as_mut().unwrap().pointer.as_mut().unwrap();
+        let Some(seat) = state.seats.iter_mut().find(|seat| {
+            seat.pointer
+                .as_ref()
+                .is_some_and(|pointer| pointer.pointer.as_ref() == Some(proxy))
+        }) else {
+            return;
+        };
+        let pointer = seat.pointer.as_mut().unwrap();
 
         match event {
+            wl_pointer::Event::Enter {
+                serial: _,
+                surface,
+                surface_x,
+                surface_y,
+            } => {
+                pointer.x = surface_x;
+                pointer.y = surface_y;
+
+                if let Some(tree) = tree_mut(&mut state.outputs, &surface) {
+                    tree.pointer_motion(surface_x as f32, surface_y as f32);
+                }
+                pointer.surface = Some(surface);
+            }
+            wl_pointer::Event::Leave { serial: _, surface } => {
+                pointer.surface = None;
+                pointer.scroll = Scroll::default();
+
+                if let Some(tree) = tree_mut(&mut state.outputs, &surface) {
+                    tree.pointer_leave();
+                }
+            }
             wl_pointer::Event::Motion {
                 time: _,
                 surface_x,
                 surface_y,
             } => {
-                pointer.x = surface_x as i64;
-                pointer.y = surface_y as i64;
+                pointer.x = surface_x;
+                pointer.y = surface_y;
+
+                if let Some(tree) = pointer
+                    .surface
+                    .as_ref()
+                    .and_then(|surface| tree_mut(&mut state.outputs, surface))
+                {
+                    tree.pointer_motion(surface_x as f32, surface_y as f32);
+                }
             }
             wl_pointer::Event::Button {
                 serial: _,
                 time: _,
                 button,
-                state,
+                state: WEnum::Value(wl_pointer::ButtonState::Pressed),
             } => {
-                println!("x: {}, y: {}", pointer.x, pointer.y);
-                println!("{} {:#?}", button, state);
+                if let Some(tree) = pointer
+                    .surface
+                    .as_ref()
+                    .and_then(|surface| tree_mut(&mut state.outputs, surface))
+                {
+                    tree.press(pointer.x as f32, pointer.y as f32);
+                    tree.click(
+                        button.into(),
+                        seat.modifiers,
+                        pointer.x as f32,
+                        pointer.y as f32,
+                    );
+                }
             }
+            wl_pointer::Event::Button {
+                state: WEnum::Value(wl_pointer::ButtonState::Released),
+                ..
+            } => {
+                if let Some(tree) = pointer
+                    .surface
+                    .as_ref()
+                    .and_then(|surface| tree_mut(&mut state.outputs, surface))
+                {
+                    tree.release();
+                }
+            }
+            wl_pointer::Event::AxisSource {
+                axis_source: WEnum::Value(source),
+            } => {
+                pointer.scroll.source = Some(match source {
+                    wl_pointer::AxisSource::Finger => input::ScrollSource::Finger,
+                    wl_pointer::AxisSource::Continuous => input::ScrollSource::Continuous,
+                    wl_pointer::AxisSource::WheelTilt => input::ScrollSource::WheelTilt,
+                    _ => input::ScrollSource::Wheel,
+                });
+            }
+            wl_pointer::Event::Axis {
+                time: _,
+                axis: WEnum::Value(axis),
+                value,
+            } => {
+                pointer.scroll.delta[axis_index(axis)] += value;
+
+                // Before version 5 there are no frames, every event stands on its own
+                if proxy.version() < 5 {
+                    scroll(pointer, seat.modifiers, &mut state.outputs);
+                }
+            }
+            wl_pointer::Event::AxisDiscrete {
+                axis: WEnum::Value(axis),
+                discrete,
+            } => {
+                pointer.scroll.discrete[axis_index(axis)] += discrete;
+            }
+            wl_pointer::Event::AxisValue120 {
+                axis: WEnum::Value(axis),
+                value120,
+            } => {
+                pointer.scroll.value120[axis_index(axis)] += value120;
+            }
+            wl_pointer::Event::AxisStop {
+                time: _,
+                axis: WEnum::Value(axis),
+            } => {
+                pointer.scroll.stop(axis_index(axis));
+            }
+            wl_pointer::Event::Frame => scroll(pointer, seat.modifiers, &mut state.outputs),
             _ => {}
         }
     }
 }
+
+/// Sends what scrolled since the last frame to the bar under the pointer
+fn scroll(pointer: &mut Pointer, modifiers: input::Modifiers, outputs: &mut [output::Output]) {
+    let Some((source, steps, delta)) = pointer.scroll.take() else {
+        return;
+    };
+
+    if let Some(tree) = pointer
+        .surface
+        .as_ref()
+        .and_then(|surface| tree_mut(outputs, surface))
+    {
+        tree.scroll(input::Scroll {
+            source,
+            steps,
+            delta,
+            modifiers,
+            x: pointer.x as f32,
+            y: pointer.y as f32,
+        });
+    }
+}
@@ -0,0 +1,150 @@
+use wayland_client::{
+    protocol::{wl_keyboard, wl_surface},
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
+};
+use xkbcommon::xkb;
+
+use super::tree_mut;
+use crate::{input, StatusBar};
+
+/// Only focuses bars with keyboard navigation enabled, which the compositor does when they're
+/// clicked
+pub struct Keyboard {
+    keyboard: wl_keyboard::WlKeyboard,
+    context: xkb::Context,
+    /// `None` until the compositor sends the keymap
+    state: Option<xkb::State>,
+    /// Surface with keyboard focus, between `Enter` and `Leave`
+    surface: Option<wl_surface::WlSurface>,
+}
+
+impl Keyboard {
+    pub fn new(keyboard: wl_keyboard::WlKeyboard) -> Self {
+        Self {
+            keyboard,
+            context: xkb::Context::new(xkb::CONTEXT_NO_FLAGS),
+            state: None,
+            surface: None,
+        }
+    }
+
+    /// Keyboards can only be released since version 3, older ones are just forgotten
+    pub fn release(self) {
+        if self.keyboard.version() >= 3 {
+            self.keyboard.release();
+        }
+    }
+
+    fn modifiers(&self) -> input::Modifiers {
+        let Some(state) = self.state.as_ref() else {
+            return input::Modifiers::default();
+        };
+
+        let active = |name| state.mod_name_is_active(name, xkb::STATE_MODS_EFFECTIVE);
+        input::Modifiers {
+            shift: active(xkb::MOD_NAME_SHIFT),
+            ctrl: active(xkb::MOD_NAME_CTRL),
+            alt: active(xkb::MOD_NAME_ALT),
+            logo: active(xkb::MOD_NAME_LOGO),
+        }
+    }
+}
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for StatusBar {
+    fn event(
+        state: &mut Self,
+        proxy: &wl_keyboard::WlKeyboard,
+        event: wl_keyboard::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let Some(seat) = state.seats.iter_mut().find(|seat| {
+            seat.keyboard
+                .as_ref()
+                .is_some_and(|keyboard| keyboard.keyboard == *proxy)
+        }) else {
+            return;
+        };
+        let keyboard = seat.keyboard.as_mut().unwrap();
+
+        match event {
+            wl_keyboard::Event::Keymap {
+                format: WEnum::Value(wl_keyboard::KeymapFormat::XkbV1),
+                fd,
+                size,
+            } => {
+                let keymap = unsafe {
+                    xkb::Keymap::new_from_fd(
+                        &keyboard.context,
+                        fd,
+                        size as usize,
+                        xkb::KEYMAP_FORMAT_TEXT_V1,
+                        xkb::KEYMAP_COMPILE_NO_FLAGS,
+                    )
+                };
+
+                keyboard.state = match keymap {
+                    Ok(Some(keymap)) => Some(xkb::State::new(&keymap)),
+                    Ok(None) => {
+                        log::warn!("failed to compile keymap");
+                        None
+                    }
+                    Err(err) => {
+                        log::warn!("failed to read keymap: {err}");
+                        None
+                    }
+                };
+            }
+            wl_keyboard::Event::Enter { surface, .. } => keyboard.surface = Some(surface),
+            // No more modifier updates come without focus, so held ones would never be released
+            wl_keyboard::Event::Leave { .. } => {
+                keyboard.surface = None;
+                seat.modifiers = input::Modifiers::default();
+            }
+            wl_keyboard::Event::Modifiers {
+                serial: _,
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+            } => {
+                if let Some(xkb_state) = keyboard.state.as_mut() {
+                    xkb_state.update_mask(mods_depressed, mods_latched, mods_locked, 0, 0, group);
+                }
+                seat.modifiers = keyboard.modifiers();
+            }
+            wl_keyboard::Event::Key {
+                serial: _,
+                time: _,
+                key,
+                state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+            } => {
+                let (Some(xkb_state), Some(surface)) =
+                    (keyboard.state.as_ref(), keyboard.surface.as_ref())
+                else {
+                    return;
+                };
+                let Some(tree) = tree_mut(&mut state.outputs, surface) else {
+                    return;
+                };
+
+                // Evdev codes are offset by 8 in xkb. Shift+Tab is ISO_Left_Tab in most keymaps.
+                match xkb_state.key_get_one_sym(xkb::Keycode::new(key + 8)) {
+                    xkb::Keysym::Tab if seat.modifiers.shift => tree.move_focus(false),
+                    xkb::Keysym::Tab | xkb::Keysym::Right | xkb::Keysym::Down => {
+                        tree.move_focus(true)
+                    }
+                    xkb::Keysym::ISO_Left_Tab | xkb::Keysym::Left | xkb::Keysym::Up => {
+                        tree.move_focus(false)
+                    }
+                    xkb::Keysym::Return | xkb::Keysym::KP_Enter | xkb::Keysym::space => {
+                        tree.activate(seat.modifiers)
+                    }
+                    _ => false,
+                };
+            }
+            _ => {}
+        }
+    }
+}
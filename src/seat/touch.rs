@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle, RegistrationToken,
+};
+use wayland_client::{
+    protocol::{wl_surface, wl_touch},
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+
+use super::tree_mut;
+use crate::{input, tree, StatusBar};
+
+/// How long a finger has to stay down for a long-press
+const LONG_PRESS: Duration = Duration::from_millis(500);
+
+/// How far a finger may wander, in logical pixels, and still tap
+const TAP_SLOP: f64 = 10.0;
+
+/// Fingers on the touchscreen, each on the surface it went down on
+pub struct Touch {
+    touch: wl_touch::WlTouch,
+    points: Vec<Point>,
+}
+
+struct Point {
+    /// Only unique while the finger is down
+    id: i32,
+    surface: wl_surface::WlSurface,
+    start: (f64, f64),
+    x: f64,
+    y: f64,
+    /// Until it fires or the point stops being a tap or long-press
+    long_press: Option<RegistrationToken>,
+    /// Moved too far, or already long-pressed, so lifting it doesn't tap
+    done: bool,
+}
+
+impl Touch {
+    pub fn new(touch: wl_touch::WlTouch) -> Self {
+        Self {
+            touch,
+            points: Vec::new(),
+        }
+    }
+
+    /// Releases the touch device and stops waiting for long-presses
+    pub fn release(self, loop_handle: &LoopHandle<'static, StatusBar>) {
+        self.points
+            .into_iter()
+            .filter_map(|point| point.long_press)
+            .for_each(|token| loop_handle.remove(token));
+
+        if self.touch.version() >= 3 {
+            self.touch.release();
+        }
+    }
+}
+
+impl Point {
+    fn position(&self) -> (f32, f32) {
+        (self.x as f32, self.y as f32)
+    }
+
+    /// Ends the active state of what this finger pressed when it went down, other fingers may
+    /// still be holding theirs
+    fn release(&self, tree: &mut tree::Tree) {
+        tree.release_at(self.start.0 as f32, self.start.1 as f32);
+    }
+}
+
+impl StatusBar {
+    /// Clicks with the secondary button once a finger was held down long enough, see
+    /// `input::Button`
+    fn long_press(&mut self, seat_id: u32, id: i32) {
+        let Some(seat) = self.seats.iter_mut().find(|seat| seat.id == seat_id) else {
+            return;
+        };
+        let Some(point) = seat
+            .touch
+            .as_mut()
+            .and_then(|touch| touch.points.iter_mut().find(|point| point.id == id))
+        else {
+            return;
+        };
+
+        point.long_press = None;
+        point.done = true;
+
+        let (x, y) = point.position();
+        if let Some(tree) = tree_mut(&mut self.outputs, &point.surface) {
+            tree.click(input::Button::Right, seat.modifiers, x, y);
+        }
+    }
+}
+
+impl Dispatch<wl_touch::WlTouch, ()> for StatusBar {
+    fn event(
+        state: &mut Self,
+        proxy: &wl_touch::WlTouch,
+        event: wl_touch::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let Some(seat) = state.seats.iter_mut().find(|seat| {
+            seat.touch
+                .as_ref()
+                .is_some_and(|touch| touch.touch == *proxy)
+        }) else {
+            return;
+        };
+        let touch = seat.touch.as_mut().unwrap();
+
+        match event {
+            wl_touch::Event::Down {
+                serial: _,
+                time: _,
+                surface,
+                id,
+                x,
+                y,
+            } => {
+                let seat_id = seat.id;
+                let long_press = state
+                    .loop_handle
+                    .insert_source(Timer::from_duration(LONG_PRESS), move |_, _, state| {
+                        state.long_press(seat_id, id);
+                        TimeoutAction::Drop
+                    })
+                    .inspect_err(|err| log::warn!("long-press disabled: {err}"))
+                    .ok();
+
+                if let Some(tree) = tree_mut(&mut state.outputs, &surface) {
+                    tree.press(x as f32, y as f32);
+                }
+
+                touch.points.push(Point {
+                    id,
+                    surface,
+                    start: (x, y),
+                    x,
+                    y,
+                    long_press,
+                    done: false,
+                });
+            }
+            wl_touch::Event::Motion { time: _, id, x, y } => {
+                let Some(point) = touch.points.iter_mut().find(|point| point.id == id) else {
+                    return;
+                };
+
+                point.x = x;
+                point.y = y;
+
+                // Dragging isn't tapping
+                if (x - point.start.0).hypot(y - point.start.1) > TAP_SLOP {
+                    if let Some(token) = point.long_press.take() {
+                        state.loop_handle.remove(token);
+                    }
+                    point.done = true;
+                }
+            }
+            wl_touch::Event::Up {
+                serial: _,
+                time: _,
+                id,
+            } => {
+                let Some(index) = touch.points.iter().position(|point| point.id == id) else {
+                    return;
+                };
+                let point = touch.points.swap_remove(index);
+
+                if let Some(token) = point.long_press {
+                    state.loop_handle.remove(token);
+                }
+
+                let (x, y) = point.position();
+                if let Some(tree) = tree_mut(&mut state.outputs, &point.surface) {
+                    if !point.done {
+                        tree.click(input::Button::Left, seat.modifiers, x, y);
+                    }
+                    point.release(tree);
+                }
+            }
+            // The compositor took the fingers over, e.g. for a gesture
+            wl_touch::Event::Cancel => {
+                for point in touch.points.drain(..) {
+                    if let Some(token) = point.long_press {
+                        state.loop_handle.remove(token);
+                    }
+
+                    if let Some(tree) = tree_mut(&mut state.outputs, &point.surface) {
+                        point.release(tree);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
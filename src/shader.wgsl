@@ -1,9 +1,38 @@
 struct ProjectionUniform {
     projection: mat4x4<f32>,
+    // Physical pixels per logical one, positions are in logical pixels
+    scale: f32,
 };
 @group(0) @binding(0)
 var<uniform> projection: ProjectionUniform;
 
+@group(1) @binding(0)
+var glyph_atlas: texture_2d<f32>;
+@group(1) @binding(1)
+var glyph_sampler: sampler;
+
+@group(2) @binding(0)
+var image_atlas: texture_2d<f32>;
+@group(2) @binding(1)
+var image_sampler: sampler;
+
+const KIND_GLYPH: u32 = 1u;
+const KIND_IMAGE: u32 = 2u;
+
+// Discriminants of `BorderStyle`, outline styles are mapped onto them
+const STYLE_NONE: u32 = 0u;
+const STYLE_SOLID: u32 = 1u;
+const STYLE_DOTTED: u32 = 2u;
+const STYLE_DASHED: u32 = 3u;
+const STYLE_DOUBLE: u32 = 4u;
+const STYLE_GROOVE: u32 = 5u;
+const STYLE_RIDGE: u32 = 6u;
+const STYLE_INSET: u32 = 7u;
+const STYLE_OUTSET: u32 = 8u;
+const STYLE_HIDDEN: u32 = 9u;
+
+const QUARTER_PI: f32 = 0.78539816339;
+
 struct VertexInput {
     @location(0) position: vec2<f32>,
 };
@@ -12,12 +41,21 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
     @location(1) rect_dim: vec4<f32>,
-    // rect_pos: vec2<f32>
-    // rect_size: vec2<f32>
+    // border_box_pos: vec2<f32>
+    // border_box_size: vec2<f32>
     @location(2) rect_color: vec4<f32>,
     @location(3) border_radius: vec4<f32>,
+    // top_left: f32
+    // top_right: f32
+    // bottom_right: f32
+    // bottom_left: f32
     @location(4) border_size: vec4<f32>,
-    @location(5) border_color: vec4<f32>,
+    // top: f32
+    // right: f32
+    // bottom: f32
+    // left: f32
+    @location(5) @interpolate(flat) border_color: vec4<u32>,
+    // Packed 8 bit RGBA per side, in the same order as border_size
     @location(6) outline: vec4<f32>,
     // outline_width: vec2<f32>
     // outline_offset: vec2<f32>
@@ -27,7 +65,23 @@ struct VertexOutput {
     // saturate: f32,
     // contrast: f32,
     // invert: f32,
-    @location(9) grayscale: f32,
+    @location(9) texture: vec4<f32>,
+    @location(10) texture_coords_image_spacing: vec4<f32>,
+    // texture_coords: vec2<f32>
+    // image_spacing: vec2<f32>, the same at every vertex
+    @location(11) image: vec4<f32>,
+    // image_offset: vec2<f32>
+    // image_size: vec2<f32>
+    @location(12) @interpolate(flat) flags: vec4<u32>,
+    // kind: u32
+    // repeat: u32, x in bit 0 and y in bit 1
+    // styles: u32, border style in byte 0 and outline style in byte 1
+    // shadow_inset: u32
+    @location(13) shadow_grayscale: vec4<f32>,
+    // shadow_offset: vec2<f32>
+    // shadow_softness: f32
+    // grayscale: f32
+    @location(14) shadow_color: vec4<f32>,
 };
 
 struct InstanceInput {
@@ -35,21 +89,40 @@ struct InstanceInput {
     @location(2) rect_color: vec4<f32>,
     @location(3) border_radius: vec4<f32>,
     @location(4) border_size: vec4<f32>,
-    @location(5) border_color: vec4<f32>,
-    @location(6) outline: vec2<f32>,
-    // outline_width: f32
-    // outline_offset: f32
-    @location(7) outline_color: vec4<f32>,
-    @location(8) filters: vec4<f32>,
+    @location(5) border_color: vec4<u32>,
+    @location(6) outline_color: vec4<f32>,
+    @location(7) filters: vec4<f32>,
     // brightness: f32,
     // saturate: f32,
     // contrast: f32,
     // invert: f32,
-    @location(9) grayscale: f32,
-    @location(10) scale: vec2<f32>,
-    @location(11) rotation: f32,
-    @location(12) translate: vec2<f32>,
-    @location(13) skew: vec2<f32>,
+    @location(8) outline_grayscale_rotation: vec4<f32>,
+    // outline_width: f32
+    // outline_offset: f32
+    // grayscale: f32
+    // rotation: f32
+    @location(9) scale_translate: vec4<f32>,
+    // scale: vec2<f32>
+    // translate: vec2<f32>
+    @location(10) skew_image_spacing: vec4<f32>,
+    // skew: vec2<f32>
+    // image_spacing: vec2<f32>
+    @location(11) texture: vec4<f32>,
+    // atlas_pos: vec2<f32>
+    // atlas_size: vec2<f32>
+    @location(12) image: vec4<f32>,
+    // image_offset: vec2<f32>
+    // image_size: vec2<f32>
+    @location(13) shadow: vec4<f32>,
+    // shadow_offset: vec2<f32>
+    // shadow_softness: f32
+    // shadow_reach: f32
+    @location(14) shadow_color: vec4<f32>,
+    @location(15) flags: vec4<u32>,
+    // kind: u32
+    // repeat: u32
+    // styles: u32
+    // shadow_inset: u32
 }
 
 fn rotation_matrix(angle: f32) -> mat2x2<f32> {
@@ -77,36 +150,48 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
 
-    let outline_width = vec2<f32>(instance.outline.x, instance.outline.x) * instance.scale;
-    let outline_offset = vec2<f32>(instance.outline.y, instance.outline.y) * instance.scale;
+    let outline = instance.outline_grayscale_rotation.xy;
+    let grayscale = instance.outline_grayscale_rotation.z;
+    let rotation = instance.outline_grayscale_rotation.w;
+    let scale = instance.scale_translate.xy;
+    let translate = instance.scale_translate.zw;
+    let skew = instance.skew_image_spacing.xy;
+    let image_spacing = instance.skew_image_spacing.zw;
+
+    let outline_width = vec2<f32>(outline.x, outline.x) * scale;
+    let outline_offset = vec2<f32>(outline.y, outline.y) * scale;
+    // Room around the outline left for the shadow
+    let shadow_reach = vec2<f32>(instance.shadow.w, instance.shadow.w) * scale;
 
     let scaled_dimensions = vec4<f32>(
-        (instance.dimensions.xy + instance.translate) * instance.scale,
-        instance.dimensions.zw * instance.scale
+        (instance.dimensions.xy + translate) * scale,
+        instance.dimensions.zw * scale
     );
     let position = model.position * scaled_dimensions.zw + scaled_dimensions.xy;
-    out.clip_position = projection.projection * vec4<f32>(position * rotation_matrix(instance.rotation) * skew_matrix(instance.skew.x, instance.skew.y), 0.0, 1.0);
+    out.clip_position = projection.projection * vec4<f32>(position * rotation_matrix(rotation) * skew_matrix(skew.x, skew.y), 0.0, 1.0);
 
     out.uv = position;
     out.rect_color = instance.rect_color;
+    let around_box = outline_width + outline_offset + shadow_reach;
     out.rect_dim = vec4<f32>(
-        scaled_dimensions.xy 
-        + vec2<f32>(instance.border_size.x, instance.border_size.y) * instance.scale
-        + vec2<f32>(outline_width + outline_offset),
-        scaled_dimensions.zw 
-        - vec2<f32>(
-            (instance.border_size.x + instance.border_size.z) * instance.scale.x, 
-            (instance.border_size.y + instance.border_size.w) * instance.scale.y
-        )
-        - vec2<f32>(outline_width * 2 + outline_offset * 2)
+        scaled_dimensions.xy + around_box,
+        scaled_dimensions.zw - around_box * 2.0
     );
-    out.border_radius = instance.border_radius * vec4<f32>(instance.scale, instance.scale);
-    out.border_size = vec4<f32>(instance.border_size.xy, instance.border_size.zw) * vec4<f32>(instance.scale, instance.scale);
+    out.border_radius = instance.border_radius * vec4<f32>(scale, scale);
+    out.border_size = instance.border_size * scale.yxyx;
     out.border_color = instance.border_color;
     out.outline = vec4<f32>(outline_width, outline_offset);
     out.outline_color = instance.outline_color;
     out.filters = instance.filters;
-    out.grayscale = instance.grayscale;
+    out.texture = instance.texture;
+    out.texture_coords_image_spacing = vec4<f32>(
+        instance.texture.xy + model.position * instance.texture.zw,
+        image_spacing * scale
+    );
+    out.image = instance.image * vec4<f32>(scale, scale);
+    out.flags = instance.flags;
+    out.shadow_grayscale = vec4<f32>(instance.shadow.xy * scale, instance.shadow.z * scale.x, grayscale);
+    out.shadow_color = instance.shadow_color;
 
     return out;
 }
@@ -114,16 +199,36 @@ fn vs_main(
 // MIT License. © 2023 Inigo Quilez, Munrocket
 // https://gist.github.com/munrocket/30e645d584b5300ee69295e54674b3e4
 // https://compute.toys/view/398
+//
+// Radii are clockwise from the top left with y pointing down, and clamped to fit the box
 fn sdf_rounded_rect(p: vec2<f32>, b: vec2<f32>, r: vec4<f32>) -> f32 {
-    var x = r.x;
-    var y = r.y;
-    x = select(r.z, r.x, p.x > 0.0);
-    y = select(r.w, r.y, p.x > 0.0);
-    x = select(y, x, p.y > 0.0);
+    let top = select(r.x, r.y, p.x > 0.0);
+    let bottom = select(r.w, r.z, p.x > 0.0);
+    let x = min(select(top, bottom, p.y > 0.0), min(b.x, b.y));
     let q = abs(p) - b + x;
     return min(max(q.x, q.y), 0.0) + length(max(q, vec2<f32>(0.0))) - x;
 }
 
+fn sdf_box(p: vec2<f32>, pos: vec2<f32>, size: vec2<f32>, r: vec4<f32>) -> f32 {
+    return sdf_rounded_rect(p - pos - (size / 2.0), size / 2.0, r);
+}
+
+// Antialiased coverage of a shape at signed distance `dist` from its edge, fading over two
+// physical pixels
+fn fill(dist: f32) -> f32 {
+    return 1.0 - smoothstep(0.0, 2.0 / projection.scale, dist);
+}
+
+// Corners of a box inside a border follow the outer corners, like CSS
+fn inner_radii(r: vec4<f32>, widths: vec4<f32>) -> vec4<f32> {
+    return max(r - max(widths.wxyz, widths), vec4<f32>(0.0));
+}
+
+// Corners of a box around a rounded one, square corners stay square
+fn outer_radii(r: vec4<f32>, distance: f32) -> vec4<f32> {
+    return select(vec4<f32>(0.0), r + distance, r > vec4<f32>(0.0));
+}
+
 fn brightness_matrix(brightness: f32) -> mat4x4<f32> {
     return mat4x4<f32>( 1, 0, 0, 0,
 			0, 1, 0, 0,
@@ -161,68 +266,306 @@ fn saturation_matrix(saturation: f32) -> mat4x4<f32> {
     );
 }
 
+fn apply_filters(color: vec4<f32>, filters: vec4<f32>, grayscale: f32) -> vec4<f32> {
+    let brightness = filters[0];
+    let saturate = filters[1];
+    let contrast = filters[2];
+    let invert = filters[3];
+
+    // Grayscale is the same as desaturating by that much
+    let filtered = brightness_matrix(brightness) * contrast_matrix(contrast) * saturation_matrix(saturate) * saturation_matrix(1.0 - grayscale) * color;
+
+    return vec4<f32>(mix(filtered.rgb, vec3<f32>(1.0) - filtered.rgb, invert), filtered.a);
+}
+
+// Position within the image tile along one axis, and whether `p` is covered by a tile at all
+fn image_axis(p: f32, size: f32, spacing: f32, repeat: bool) -> vec2<f32> {
+    if (size <= 0.0) {
+        return vec2<f32>(0.0, 0.0);
+    }
+
+    if (!repeat) {
+        let t = p / size;
+        return vec2<f32>(t, select(0.0, 1.0, t >= 0.0 && t < 1.0));
+    }
+
+    let period = size + spacing;
+    let m = p - floor(p / period) * period;
+    return vec2<f32>(m / size, select(0.0, 1.0, m < size));
+}
+
+// Background image color at `p`, relative to the padding box
+fn sample_image(p: vec2<f32>, in: VertexOutput) -> vec4<f32> {
+    let x = image_axis(p.x - in.image.x, in.image.z, in.texture_coords_image_spacing.z, (in.flags.y & 1u) != 0u);
+    let y = image_axis(p.y - in.image.y, in.image.w, in.texture_coords_image_spacing.w, (in.flags.y & 2u) != 0u);
+
+    // Keep half a texel away from the region edges so filtering never reads the neighbours
+    let half_texel = vec2<f32>(0.5) / vec2<f32>(textureDimensions(image_atlas));
+    let coords = in.texture.xy + clamp(
+        vec2<f32>(x.x, y.x) * in.texture.zw,
+        half_texel,
+        in.texture.zw - half_texel
+    );
+
+    let sampled = textureSampleLevel(image_atlas, image_sampler, coords, 0.0);
+    return vec4<f32>(sampled.rgb, sampled.a * x.y * y.y);
+}
+
+// Abramowitz and Stegun approximation, good to about 5e-4
+fn erf(x: f32) -> f32 {
+    let a = abs(x);
+    let t = 1.0 + (0.278393 + (0.230389 + 0.078108 * a * a) * a) * a;
+    let t2 = t * t;
+    return sign(x) * (1.0 - 1.0 / (t2 * t2));
+}
+
+// Coverage of a shape blurred by `softness` at signed distance `dist` from its edge. Like CSS the
+// blur is a gaussian with a standard deviation of half the softness
+fn shadow_coverage(dist: f32, softness: f32) -> f32 {
+    if (softness <= 0.0) {
+        return fill(dist);
+    }
+
+    let sigma = softness / 2.0;
+    return 0.5 - 0.5 * erf(dist / (sigma * 1.41421356237));
+}
+
+// Straight alpha `top` composited over `bottom`
+fn blend_over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    let alpha = top.a + bottom.a * (1.0 - top.a);
+    let rgb = top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a);
+    return vec4<f32>(rgb / max(alpha, 0.00001), alpha);
+}
+
+// Side of a box `size` big that `p` is closest to in units of border width, which splits
+// the border along the lines joining the outer and inner corners. 0 is the top, going clockwise
+fn border_side(p: vec2<f32>, size: vec2<f32>, widths: vec4<f32>) -> u32 {
+    let depths = select(
+        vec4<f32>(p.y, size.x - p.x, size.y - p.y, p.x) / widths,
+        vec4<f32>(3.4e38),
+        widths <= vec4<f32>(0.0)
+    );
+
+    var side = 0u;
+    for (var i = 1u; i < 4u; i++) {
+        if (depths[i] < depths[side]) {
+            side = i;
+        }
+    }
+
+    return side;
+}
+
+// Distance along `side` measured on the middle of the border, from the corner join where the side
+// starts, and the length of the side. Rounded corners are followed along their arc
+fn border_position(side: u32, p: vec2<f32>, size: vec2<f32>, radii: vec4<f32>, width: f32) -> vec2<f32> {
+    // Turn the box so the side runs along x at the top, starting at x = 0
+    var along: f32;
+    var across: f32;
+    var len: f32;
+    switch side {
+        case 0u: {
+            along = p.x;
+            across = p.y;
+            len = size.x;
+        }
+        case 1u: {
+            along = p.y;
+            across = size.x - p.x;
+            len = size.y;
+        }
+        case 2u: {
+            along = size.x - p.x;
+            across = size.y - p.y;
+            len = size.x;
+        }
+        default: {
+            along = size.y - p.y;
+            across = p.x;
+            len = size.y;
+        }
+    }
+
+    let start = min(radii[side], min(size.x, size.y) / 2.0);
+    let end = min(radii[(side + 1u) % 4u], min(size.x, size.y) / 2.0);
+
+    // Each side owns half of the arc of its corners
+    let start_radius = max(start - width / 2.0, 0.0);
+    let end_radius = max(end - width / 2.0, 0.0);
+    let total = start_radius * QUARTER_PI + max(len - start - end, 0.0) + end_radius * QUARTER_PI;
+
+    if (along < start && across < start) {
+        let v = vec2<f32>(start - along, start - across);
+        return vec2<f32>(max(atan2(v.y, v.x) - QUARTER_PI, 0.0) * start_radius, total);
+    }
+
+    if (len - along < end && across < end) {
+        let v = vec2<f32>(end - (len - along), end - across);
+        return vec2<f32>(total - max(atan2(v.y, v.x) - QUARTER_PI, 0.0) * end_radius, total);
+    }
+
+    return vec2<f32>(start_radius * QUARTER_PI + along - start, total);
+}
+
+// Coverage and shade of a border or outline drawn in `style`, at `depth` into a border `width` wide
+fn border_pattern(
+    style: u32,
+    side: u32,
+    p: vec2<f32>,
+    size: vec2<f32>,
+    radii: vec4<f32>,
+    width: f32,
+    depth: f32
+) -> vec2<f32> {
+    let top_left = side == 0u || side == 3u;
+    let outer_half = depth < width / 2.0;
+
+    switch style {
+        // Round dots as wide as the border, spread evenly so each side ends on a whole gap
+        case STYLE_DOTTED: {
+            let position = border_position(side, p, size, radii, width);
+            let period = position.y / max(round(position.y / (width * 2.0)), 1.0);
+            let center = (floor(position.x / period) + 0.5) * period;
+            let dist = length(vec2<f32>(position.x - center, depth - width / 2.0)) - width / 2.0;
+            return vec2<f32>(clamp(0.5 - dist, 0.0, 1.0), 1.0);
+        }
+        // Dashes twice as long as the gaps between them, spread the same way as dots
+        case STYLE_DASHED: {
+            let position = border_position(side, p, size, radii, width);
+            let period = position.y / max(round(position.y / (width * 3.0)), 1.0);
+            let center = (floor(position.x / period) + 0.5) * period;
+            let dist = abs(position.x - center) - period / 3.0;
+            return vec2<f32>(clamp(0.5 - dist, 0.0, 1.0), 1.0);
+        }
+        // Two lines with a gap between them, each a third of the width
+        case STYLE_DOUBLE: {
+            let gap = min(depth - width / 3.0, width * 2.0 / 3.0 - depth);
+            return vec2<f32>(clamp(0.5 - gap, 0.0, 1.0), 1.0);
+        }
+        // Lit from the top left, so either those sides or the others are in shadow
+        case STYLE_INSET: {
+            return vec2<f32>(1.0, select(1.0, 0.5, top_left));
+        }
+        case STYLE_OUTSET: {
+            return vec2<f32>(1.0, select(0.5, 1.0, top_left));
+        }
+        // Inset on the outer half and outset on the inner one, or the other way around
+        case STYLE_GROOVE: {
+            return vec2<f32>(1.0, select(1.0, 0.5, top_left == outer_half));
+        }
+        case STYLE_RIDGE: {
+            return vec2<f32>(1.0, select(1.0, 0.5, top_left != outer_half));
+        }
+        default: {
+            return vec2<f32>(1.0, 1.0);
+        }
+    }
+}
+
+fn is_drawn(style: u32) -> bool {
+    return style != STYLE_NONE && style != STYLE_HIDDEN;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let outline_width = in.outline.xy;
-    let outline_offset = in.outline.zw;
-
-    let brightness = in.filters[0];
-    let saturate = in.filters[1];
-    let contrast = in.filters[2];
-    let invert = in.filters[3];
-
-    var pos: vec2<f32> = in.rect_dim.xy;
-    var size: vec2<f32> = in.rect_dim.zw;
-    var dist: f32 = sdf_rounded_rect(
-        in.uv - pos - (size / 2.0),
-        size / 2.0,
-        in.border_radius
-    );
-    let rect_alpha = 1.0 - smoothstep(0.0, 2.0, dist);
+    // Glyphs only carry coverage, the color comes from the instance
+    if (in.flags.x == KIND_GLYPH) {
+        let coverage = textureSampleLevel(glyph_atlas, glyph_sampler, in.texture_coords_image_spacing.xy, 0.0).r;
+        return apply_filters(vec4<f32>(in.rect_color.rgb, in.rect_color.a * coverage), in.filters, in.shadow_grayscale.w);
+    }
+
+    let outline_width = in.outline.x;
+    let outline_offset = in.outline.z;
+    let border_style = in.flags.z & 0xffu;
+    let outline_style = (in.flags.z >> 8u) & 0xffu;
+
+    let box_pos = in.rect_dim.xy;
+    let box_size = in.rect_dim.zw;
+    let box_dist = sdf_box(in.uv, box_pos, box_size, in.border_radius);
+
+    // The background fills the padding box, inside the border
+    let widths = in.border_size;
+    let pos = box_pos + widths.wx;
+    let size = max(box_size - widths.yx - widths.wz, vec2<f32>(0.0));
+    let radii = inner_radii(in.border_radius, widths);
+    let rect_alpha = fill(sdf_box(in.uv, pos, size, radii));
+
     var color: vec4<f32> = vec4<f32>(in.rect_color.rgb, in.rect_color.a * rect_alpha);
 
-    if (in.border_size.x > 0.0 || in.border_size.y > 0.0 || in.border_size.w > 0.0 || in.border_size.z > 0.0) {
-	size += vec2<f32>((in.border_size.x + in.border_size.z), (in.border_size.y + in.border_size.w)) / 2.0;
-    	pos -= vec2<f32>(in.border_size.x, in.border_size.y) / 2.0;
-    	let border_dist = sdf_rounded_rect(
-    	    in.uv - pos - (size / 2.0),
-    	    size / 2.0,
-    	    in.border_radius
-    	);
-    	let border_alpha = 1.0 - smoothstep(0.0, 2.0, border_dist);
-    	let border_color = vec4<f32>(in.border_color.rgb, in.border_color.a * border_alpha);
-
-	color = mix(color, border_color, smoothstep(0.0, 1.0, dist));
-	dist = border_dist;
+    // Drawn over the background color and clipped to the rounded padding box
+    if (in.flags.x == KIND_IMAGE) {
+        let image = sample_image(in.uv - pos, in);
+        color = blend_over(vec4<f32>(image.rgb, image.a * rect_alpha), color);
+    }
+
+    let has_shadow = in.shadow_color.a > 0.0;
+
+    // Inset shadows darken the padding box everywhere the offset box doesn't cover it
+    if (has_shadow && in.flags.w != 0u) {
+        let inner_dist = sdf_box(in.uv, pos + in.shadow_grayscale.xy, size, radii);
+        let shadow_alpha = in.shadow_color.a * (1.0 - shadow_coverage(inner_dist, in.shadow_grayscale.z));
+        color = blend_over(vec4<f32>(in.shadow_color.rgb, shadow_alpha * rect_alpha), color);
     }
 
-    if (outline_offset.x > 0.0 || outline_offset.y > 0.0) {
-        size += outline_offset * 2.0;
-    	pos -= outline_offset;
-        color = mix(color, vec4<f32>(0.0), smoothstep(0.0, 1.0, dist));
-    	dist = sdf_rounded_rect(
-    	    in.uv - pos - (size / 2.0),
-    	    size / 2.0,
-    	    in.border_radius
-    	);
+    let border = clamp(fill(box_dist) - rect_alpha, 0.0, 1.0);
+    if (is_drawn(border_style) && border > 0.0) {
+        let side = border_side(in.uv - box_pos, box_size, widths);
+        let pattern = border_pattern(
+            border_style,
+            side,
+            in.uv - box_pos,
+            box_size,
+            in.border_radius,
+            widths[side],
+            -box_dist
+        );
+        let side_color = unpack4x8unorm(in.border_color[side]);
+
+        color = blend_over(
+            vec4<f32>(side_color.rgb * pattern.y, side_color.a * pattern.x * border),
+            color
+        );
     }
 
-    if (outline_width.x > 0.0 || outline_width.y > 0.0) {
-        size += outline_width * 2.0;
-    	pos -= outline_width;
-    	let outline_dist = sdf_rounded_rect(
-    	    in.uv - pos - (size / 2.0),
-    	    size / 2.0,
-    	    in.border_radius
-    	);
-    	let outline_alpha = 1.0 - smoothstep(0.0, 2.0, outline_dist);
-    	let outline_color = vec4<f32>(in.outline_color.rgb, in.outline_color.a * outline_alpha);
-
-        color = mix(color, outline_color, smoothstep(0.0, 1.0, dist));
-        dist = outline_dist;
+    // The outline follows the border box, `outline_offset` away from it
+    if (is_drawn(outline_style) && outline_width > 0.0) {
+        let gap_pos = box_pos - outline_offset;
+        let gap_size = box_size + outline_offset * 2.0;
+        let outline_pos = gap_pos - outline_width;
+        let outline_size = gap_size + outline_width * 2.0;
+        let outline_radii = outer_radii(in.border_radius, outline_offset + outline_width);
+
+        let outline_dist = sdf_box(in.uv, outline_pos, outline_size, outline_radii);
+        let gap_dist = sdf_box(in.uv, gap_pos, gap_size, outer_radii(in.border_radius, outline_offset));
+        let outline = clamp(fill(outline_dist) - fill(gap_dist), 0.0, 1.0);
+
+        if (outline > 0.0) {
+            let side = border_side(in.uv - outline_pos, outline_size, vec4<f32>(outline_width));
+            let pattern = border_pattern(
+                outline_style,
+                side,
+                in.uv - outline_pos,
+                outline_size,
+                outline_radii,
+                outline_width,
+                -outline_dist
+            );
+
+            color = blend_over(
+                vec4<f32>(in.outline_color.rgb * pattern.y, in.outline_color.a * pattern.x * outline),
+                color
+            );
+        }
     }
 
-    color = brightness_matrix(brightness) * contrast_matrix(contrast) * saturation_matrix(saturate) * color;
+    // Drawn beneath everything else and never inside the border box, like CSS
+    if (has_shadow && in.flags.w == 0u) {
+        let shadow_dist = sdf_box(in.uv, box_pos + in.shadow_grayscale.xy, box_size, in.border_radius);
+        let shadow_alpha = in.shadow_color.a
+            * shadow_coverage(shadow_dist, in.shadow_grayscale.z)
+            * (1.0 - fill(box_dist));
+        color = blend_over(color, vec4<f32>(in.shadow_color.rgb, shadow_alpha));
+    }
 
-    return vec4<f32>(mix(color.rgb, vec3<f32>(1.0) - color.rgb, invert), color.a);
+    return apply_filters(color, in.filters, in.shadow_grayscale.w);
 }
@@ -0,0 +1,193 @@
+pub mod atlas;
+mod font;
+
+use crate::{buffers, rectangle::Extents};
+use std::{cell::OnceCell, rc::Rc};
+
+struct Glyph {
+    id: u16,
+    x: f32,
+    y: f32,
+}
+
+struct Shaped {
+    font: Option<Rc<font::Font>>,
+    glyphs: Vec<Glyph>,
+    width: f32,
+    height: f32,
+    ascent: f32,
+}
+
+pub struct Text {
+    content: String,
+    family: String,
+    size: f32,
+    weight: u16,
+    color: [f32; 4],
+    // Shaping needs the font, so it's done lazily and redone whenever a setter invalidates it
+    shaped: OnceCell<Shaped>,
+}
+
+impl Text {
+    pub fn new(content: &str) -> Self {
+        Self {
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn set_content(mut self, content: &str) -> Self {
+        self.content = content.to_string();
+        self.shaped = OnceCell::new();
+        self
+    }
+
+    pub fn set_family(mut self, family: &str) -> Self {
+        self.family = family.to_string();
+        self.shaped = OnceCell::new();
+        self
+    }
+
+    pub fn set_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self.shaped = OnceCell::new();
+        self
+    }
+
+    pub fn set_weight(mut self, weight: u16) -> Self {
+        self.weight = weight;
+        self.shaped = OnceCell::new();
+        self
+    }
+
+    pub fn set_color(mut self, r: f32, g: f32, b: f32, a: f32) -> Self {
+        self.color = [r, g, b, a];
+        self
+    }
+
+    pub fn get_extents(&self) -> Extents {
+        let shaped = self.shaped();
+
+        Extents {
+            x: 0.0,
+            y: 0.0,
+            width: shaped.width,
+            height: shaped.height,
+        }
+    }
+
+    fn shaped(&self) -> &Shaped {
+        self.shaped.get_or_init(|| {
+            let font = font::load(&self.family, self.weight);
+            let mut shaped = Shaped {
+                font: font.clone(),
+                glyphs: Vec::new(),
+                width: 0.0,
+                height: 0.0,
+                ascent: 0.0,
+            };
+
+            let Some(face) = font
+                .as_ref()
+                .and_then(|font| rustybuzz::Face::from_slice(&font.data, font.index))
+            else {
+                return shaped;
+            };
+
+            let scale = self.size / face.units_per_em() as f32;
+
+            let mut buffer = rustybuzz::UnicodeBuffer::new();
+            buffer.push_str(&self.content);
+            let output = rustybuzz::shape(&face, &[], buffer);
+
+            let mut pen = 0.0;
+            output
+                .glyph_infos()
+                .iter()
+                .zip(output.glyph_positions())
+                .for_each(|(info, position)| {
+                    shaped.glyphs.push(Glyph {
+                        id: info.glyph_id as u16,
+                        x: pen + position.x_offset as f32 * scale,
+                        y: -position.y_offset as f32 * scale,
+                    });
+                    pen += position.x_advance as f32 * scale;
+                });
+
+            shaped.width = pen;
+            shaped.ascent = face.ascender() as f32 * scale;
+            shaped.height = (face.ascender() - face.descender()) as f32 * scale;
+
+            shaped
+        })
+    }
+
+    /// Appends one instance per visible glyph, with the text's top left corner at `position`.
+    /// `base` supplies filters and transforms, so text follows the node it belongs to. Glyphs are
+    /// rasterized for `scale` physical pixels per logical one.
+    pub fn collect_instances(
+        &self,
+        (x, y): (f32, f32),
+        base: &buffers::Instance,
+        atlas: &mut atlas::GlyphAtlas,
+        queue: &wgpu::Queue,
+        scale: f32,
+        instances: &mut Vec<buffers::Instance>,
+    ) {
+        let shaped = self.shaped();
+        let Some(font) = shaped.font.as_ref() else {
+            return;
+        };
+
+        // Glyphs are rasterized at their size in physical pixels and placed on whole physical
+        // pixels, anything else blurs them. Positions are worked out in physical pixels and
+        // turned back into logical ones, which the projection scales again.
+        let baseline = ((y + shaped.ascent) * scale).round();
+
+        shaped.glyphs.iter().for_each(|glyph| {
+            let Some(rasterized) = atlas.get(queue, font, glyph.id, self.size * scale) else {
+                return;
+            };
+
+            let metrics = rasterized.metrics;
+            let left = ((x + glyph.x) * scale).round() + metrics.xmin as f32;
+            let top = baseline + (glyph.y * scale).round()
+                - (metrics.ymin + metrics.height as i32) as f32;
+
+            instances.push(buffers::Instance {
+                dimensions: [
+                    left / scale,
+                    top / scale,
+                    metrics.width as f32 / scale,
+                    metrics.height as f32 / scale,
+                ],
+                color: self.color,
+                border_radius: [0.0; 4],
+                border_size: [0.0; 4],
+                border_color: [0; 4],
+                outline: [0.0; 2],
+                outline_color: [0.0; 4],
+                image_spacing: [0.0; 2],
+                texture: rasterized.uv,
+                image: [0.0; 4],
+                shadow: [0.0; 4],
+                shadow_color: [0.0; 4],
+                flags: [buffers::InstanceKind::Glyph as u32, 0, 0, 0],
+                ..*base
+            });
+        });
+    }
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self {
+            content: String::new(),
+            family: "sans-serif".to_string(),
+            size: 14.0,
+            weight: 400,
+            color: [0.0, 0.0, 0.0, 1.0],
+            shaped: OnceCell::new(),
+        }
+    }
+}
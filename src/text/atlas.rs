@@ -0,0 +1,148 @@
+use super::font::Font;
+use crate::atlas::Atlas;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy)]
+pub struct Rasterized {
+    pub metrics: fontdue::Metrics,
+    pub uv: [f32; 4],
+}
+
+/// Coverage masks of every glyph drawn so far, rasterized on first use
+pub struct GlyphAtlas {
+    atlas: Atlas,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    // Keyed by font id, glyph id and size bits. Blank glyphs like spaces, and glyphs too big for
+    // the atlas to ever hold, are cached as `None`
+    glyphs: HashMap<(usize, u16, u32), Option<Rasterized>>,
+    full: bool,
+    /// Bumped whenever cached coordinates stop being valid, see `make_room`
+    generation: u64,
+}
+
+impl GlyphAtlas {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let atlas = Atlas::new(device, wgpu::TextureFormat::R8Unorm, 1024);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Glyph sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("glyph atlas bind group layout"),
+        });
+
+        let bind_group = atlas.bind_group(
+            device,
+            &bind_group_layout,
+            &sampler,
+            "glyph atlas bind group",
+        );
+
+        Self {
+            atlas,
+            bind_group_layout,
+            bind_group,
+            sampler,
+            glyphs: HashMap::new(),
+            full: false,
+            generation: 0,
+        }
+    }
+
+    pub fn get(
+        &mut self,
+        queue: &wgpu::Queue,
+        font: &Font,
+        id: u16,
+        size: f32,
+    ) -> Option<Rasterized> {
+        let key = (font.id, id, size.to_bits());
+
+        if let Some(rasterized) = self.glyphs.get(&key) {
+            return *rasterized;
+        }
+
+        let (metrics, bitmap) = font.raster.rasterize_indexed(id, size);
+
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+
+        let rasterized = if width == 0 || height == 0 {
+            None
+        } else if !self.atlas.could_fit(width, height) {
+            log::error!("glyph of {width}x{height} doesn't fit in the glyph atlas");
+            None
+        } else {
+            let Some(region) = self.atlas.allocate(width, height) else {
+                self.full = true;
+                return None;
+            };
+
+            self.atlas.upload(queue, region, &bitmap);
+
+            Some(Rasterized {
+                metrics,
+                uv: self.atlas.uv(region),
+            })
+        };
+
+        self.glyphs.insert(key, rasterized);
+
+        rasterized
+    }
+
+    /// Whether a glyph didn't fit since the last `make_room`, meaning some text wasn't drawn
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Grows the atlas after a glyph didn't fit, or empties it once it can't grow any more.
+    /// Either way everything in it has to be added again, by every tree drawn with it.
+    pub fn make_room(&mut self, device: &wgpu::Device) {
+        if !self.full {
+            return;
+        }
+
+        if self.atlas.grow(device) {
+            self.bind_group = self.atlas.bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.sampler,
+                "glyph atlas bind group",
+            );
+        } else {
+            self.atlas.clear();
+        }
+
+        self.glyphs.clear();
+        self.full = false;
+        self.generation += 1;
+    }
+}
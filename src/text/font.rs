@@ -0,0 +1,87 @@
+use std::{cell::RefCell, collections::HashMap, ffi::CString, rc::Rc};
+
+pub struct Font {
+    pub id: usize,
+    pub data: Vec<u8>,
+    pub index: u32,
+    pub raster: fontdue::Font,
+}
+
+type FontCache = HashMap<(String, u16), Option<Rc<Font>>>;
+
+thread_local! {
+    static FONTCONFIG: Option<fontconfig::Fontconfig> = fontconfig::Fontconfig::new();
+    // Failed lookups are cached as well, so a missing font is only reported once
+    static FONTS: RefCell<FontCache> = RefCell::default();
+}
+
+/// Finds the best match for `family` and CSS `weight` through fontconfig. Fonts are loaded once
+/// and shared by every text using them.
+pub fn load(family: &str, weight: u16) -> Option<Rc<Font>> {
+    let key = (family.to_string(), weight);
+
+    if let Some(font) = FONTS.with_borrow(|fonts| fonts.get(&key).cloned()) {
+        return font;
+    }
+
+    let font = FONTCONFIG
+        .with(|fc| find(fc.as_ref()?, family, weight))
+        .and_then(|(path, index)| {
+            let data = std::fs::read(&path)
+                .inspect_err(|err| log::error!("failed to read font {path}: {err}"))
+                .ok()?;
+
+            let settings = fontdue::FontSettings {
+                collection_index: index,
+                ..Default::default()
+            };
+            let raster = fontdue::Font::from_bytes(data.as_slice(), settings)
+                .inspect_err(|err| log::error!("failed to parse font {path}: {err}"))
+                .ok()?;
+
+            Some(Rc::new(Font {
+                id: FONTS.with_borrow(|fonts| fonts.len()),
+                data,
+                index,
+                raster,
+            }))
+        });
+
+    if font.is_none() {
+        log::error!("no font found for family {family} with weight {weight}");
+    }
+
+    FONTS.with_borrow_mut(|fonts| fonts.insert(key, font.clone()));
+
+    font
+}
+
+fn find(fc: &fontconfig::Fontconfig, family: &str, weight: u16) -> Option<(String, u32)> {
+    let family = CString::new(family).ok()?;
+
+    let mut pattern = fontconfig::Pattern::new(fc);
+    pattern.add_string(fontconfig::FC_FAMILY, &family);
+    pattern.add_integer(fontconfig::FC_WEIGHT, fc_weight(weight));
+
+    let matched = pattern.font_match();
+
+    Some((
+        matched.filename()?.to_string(),
+        matched.face_index().unwrap_or(0) as u32,
+    ))
+}
+
+// Fontconfig has its own weight scale, map CSS weights onto the closest named weight
+fn fc_weight(weight: u16) -> i32 {
+    match weight {
+        0..=149 => fontconfig::FC_WEIGHT_THIN,
+        150..=249 => fontconfig::FC_WEIGHT_EXTRALIGHT,
+        250..=349 => fontconfig::FC_WEIGHT_LIGHT,
+        350..=449 => fontconfig::FC_WEIGHT_REGULAR,
+        450..=549 => fontconfig::FC_WEIGHT_MEDIUM,
+        550..=649 => fontconfig::FC_WEIGHT_DEMIBOLD,
+        650..=749 => fontconfig::FC_WEIGHT_BOLD,
+        750..=849 => fontconfig::FC_WEIGHT_EXTRABOLD,
+        _ => fontconfig::FC_WEIGHT_BLACK,
+    }
+}
@@ -0,0 +1,826 @@
+pub mod layout;
+
+use std::ops::{Deref, DerefMut};
+
+use crate::{buffers, input, rectangle, text};
+
+#[derive(Clone, Copy)]
+pub enum Section {
+    Start,
+    Center,
+    End,
+}
+
+/// Root of a bar. Its first three children are the start, center and end sections, any children
+/// added after them are left where they are, which is useful for overlays.
+pub struct Tree {
+    node: Node,
+    /// Set by any mutable access, cleared once the change has been prepared for drawing
+    dirty: bool,
+    /// What's drawn this frame, and last frame to compare against. Both are reused so a frame
+    /// doesn't allocate once they're big enough
+    instances: Vec<buffers::Instance>,
+    previous: Vec<buffers::Instance>,
+    regions: Vec<rectangle::Extents>,
+    full_damage: bool,
+    /// Generations of the glyph and image atlases the instances point into
+    atlases: [u64; 2],
+}
+
+/// Interaction states a node can be styled for. Later ones win when several apply, so a
+/// pressed button shows its active style while also hovered.
+#[derive(Clone, Copy)]
+pub enum State {
+    Hover,
+    Focus,
+    Active,
+}
+
+/// Part of the surface that changed since the last frame, in surface coordinates
+pub enum Damage<'a> {
+    Full,
+    Regions(&'a [rectangle::Extents]),
+}
+
+impl Tree {
+    pub fn new(rectangle: rectangle::Rectangle) -> Self {
+        let section = || {
+            Node::new(rectangle::Rectangle::default())
+                .set_layout(layout::Layout::default().set_align_items(layout::AlignItems::Center))
+        };
+
+        Self {
+            node: Node::new(rectangle)
+                .with_child(section())
+                .with_child(section())
+                .with_child(section()),
+            dirty: true,
+            instances: Vec::new(),
+            previous: Vec::new(),
+            regions: Vec::new(),
+            full_damage: false,
+            atlases: [0; 2],
+        }
+    }
+
+    pub fn section_mut(&mut self, section: Section) -> &mut Node {
+        self.dirty = true;
+        &mut self.node.children[section as usize]
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Generations of the glyph and image atlases as of the last `prepare`. The instances have
+    /// to be collected again once the atlases moved on, as what they pointed to is gone.
+    pub fn atlas_generations(&self) -> [u64; 2] {
+        self.atlases
+    }
+
+    /// Forgets what was drawn last, so the next `prepare` writes every instance. Needed when the
+    /// tree is drawn with a different instance buffer than last time.
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+        self.instances.clear();
+    }
+
+    /// Runs the click handler of the top-most node at `x`, `y` in surface coordinates, or of the
+    /// closest ancestor that has one. Returns whether a handler ran.
+    pub fn click(
+        &mut self,
+        button: input::Button,
+        modifiers: input::Modifiers,
+        x: f32,
+        y: f32,
+    ) -> bool {
+        let click = input::Click {
+            button,
+            modifiers,
+            x,
+            y,
+        };
+
+        // Clicking anywhere else takes the focus away, the node handling the click gets it
+        let mut restyled = self.node.set_state_at(State::Focus, None).1;
+        self.node
+            .walk_mut(&mut |node| restyled |= std::mem::take(&mut node.focus_ring));
+
+        // Handlers may change anything about their node
+        let handled = self.node.route(x, y, &mut |node| node.click(&click)) == Some(true);
+        self.dirty |= handled || restyled;
+        handled
+    }
+
+    /// Runs the scroll handler of the top-most node at the scroll's position, in surface
+    /// coordinates, or of the closest ancestor that has one. Returns whether a handler ran.
+    pub fn scroll(&mut self, scroll: input::Scroll) -> bool {
+        let handled = self
+            .node
+            .route(scroll.x, scroll.y, &mut |node| node.scroll(&scroll))
+            == Some(true);
+        self.dirty |= handled;
+        handled
+    }
+
+    /// Moves the focus to the next or previous clickable node, parents before their children,
+    /// and shows a focus ring around it. Returns whether there was one to move to.
+    pub fn move_focus(&mut self, forward: bool) -> bool {
+        let mut count = 0;
+        let mut current = None;
+        self.node.walk_mut(&mut |node| {
+            if node.on_click.is_some() {
+                if node.is(State::Focus) {
+                    current = Some(count);
+                }
+                count += 1;
+            }
+        });
+
+        if count == 0 {
+            return false;
+        }
+
+        let next = match (current, forward) {
+            (Some(current), true) => (current + 1) % count,
+            (Some(current), false) => (current + count - 1) % count,
+            (None, true) => 0,
+            (None, false) => count - 1,
+        };
+
+        let mut index = 0;
+        self.node.walk_mut(&mut |node| {
+            let focused = node.on_click.is_some() && index == next;
+            node.states[State::Focus as usize] = focused;
+            node.focus_ring = focused;
+            index += node.on_click.is_some() as usize;
+        });
+
+        self.dirty = true;
+        true
+    }
+
+    /// Clicks the focused node in its center, as if it was clicked with the primary button.
+    /// Returns whether a node was focused.
+    pub fn activate(&mut self, modifiers: input::Modifiers) -> bool {
+        let mut activated = false;
+        self.node.walk_mut(&mut |node| {
+            if activated || !node.is(State::Focus) {
+                return;
+            }
+
+            let extents = node.data.get_extents();
+            activated = node.click(&input::Click {
+                button: input::Button::Left,
+                modifiers,
+                x: extents.x + extents.width / 2.0,
+                y: extents.y + extents.height / 2.0,
+            });
+        });
+
+        self.dirty |= activated;
+        activated
+    }
+
+    /// Hovers the nodes under the pointer at `x`, `y` in surface coordinates. Returns whether a
+    /// node with a hover style entered or left the hover state.
+    pub fn pointer_motion(&mut self, x: f32, y: f32) -> bool {
+        let restyled = self.node.set_state_at(State::Hover, Some((x, y))).1;
+        self.dirty |= restyled;
+        restyled
+    }
+
+    /// Makes the nodes at `x`, `y` in surface coordinates active until released. Nodes pressed
+    /// elsewhere stay active, e.g. under other fingers.
+    pub fn press(&mut self, x: f32, y: f32) -> bool {
+        let restyled = self.node.set_state_under(State::Active, x, y, true).1;
+        self.dirty |= restyled;
+        restyled
+    }
+
+    /// Ends the active state of the nodes pressed at `x`, `y`, leaving the others pressed
+    pub fn release_at(&mut self, x: f32, y: f32) -> bool {
+        let restyled = self.node.set_state_under(State::Active, x, y, false).1;
+        self.dirty |= restyled;
+        restyled
+    }
+
+    /// Ends the active state of every node that was pressed
+    pub fn release(&mut self) -> bool {
+        let restyled = self.node.set_state_at(State::Active, None).1;
+        self.dirty |= restyled;
+        restyled
+    }
+
+    /// Clears the hover and active states once the pointer left the surface
+    pub fn pointer_leave(&mut self) -> bool {
+        let restyled = self.node.set_state_at(State::Hover, None).1
+            | self.node.set_state_at(State::Active, None).1;
+        self.dirty |= restyled;
+        restyled
+    }
+
+    /// Sets the axis sections are laid out along, which follows the bar orientation
+    pub fn set_direction(&mut self, direction: layout::Direction) {
+        self.dirty = true;
+        self.node.children[..3]
+            .iter_mut()
+            .filter_map(|section| section.layout.as_mut())
+            .for_each(|layout| layout.direction = direction);
+    }
+
+    /// Anchors the sections to the start, center and end of the bar, then lays out their contents.
+    /// The center section is centered on the whole bar, not on the space left by the others.
+    pub fn layout(&mut self) {
+        self.dirty = true;
+        let content = self.node.data.get_content_extents();
+
+        let sections = &mut self.node.children[..3];
+        let direction = sections[0]
+            .layout
+            .map(|layout| layout.direction)
+            .unwrap_or_default();
+
+        let (origin, origin_cross) = direction.axes(content.x, content.y);
+        let (available, cross) = direction.axes(content.width, content.height);
+
+        sections
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, section)| {
+                let main = section
+                    .layout
+                    .map(|layout| layout.natural_main(&section.children))
+                    .unwrap_or(0.0)
+                    .min(available);
+
+                let offset = match index {
+                    0 => 0.0,
+                    1 => (available - main) / 2.0,
+                    _ => available - main,
+                };
+
+                let (x, y) = direction.axes(origin + offset, origin_cross);
+                let (width, height) = direction.axes(main, cross);
+
+                section.set_layout_extents(x, y, width, height);
+            });
+
+        self.node
+            .children
+            .iter_mut()
+            .for_each(|child| child.layout());
+    }
+
+    /// Collects what to draw, compares it with the last frame and writes what changed to
+    /// `instance_buffer`. Returns `false` when nothing visible changed, so there's no need to
+    /// draw at all. Text is rasterized for `scale` physical pixels per logical one.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instance_buffer: &mut buffers::InstanceBuffer,
+        glyph_atlas: &mut text::atlas::GlyphAtlas,
+        image_atlas: &mut rectangle::image::ImageAtlas,
+        scale: f32,
+    ) -> bool {
+        self.dirty = false;
+
+        std::mem::swap(&mut self.instances, &mut self.previous);
+        self.instances.clear();
+        self.node
+            .collect_instances(&mut self.instances, glyph_atlas, image_atlas, queue, scale);
+
+        // The atlases are shared by every output, so they grow to hold what all of them draw.
+        // Only once they can't grow any more do they start over with what this frame needs.
+        if glyph_atlas.is_full() || image_atlas.is_full() {
+            glyph_atlas.make_room(device);
+            image_atlas.make_room(device);
+            self.instances.clear();
+            self.node.collect_instances(
+                &mut self.instances,
+                glyph_atlas,
+                image_atlas,
+                queue,
+                scale,
+            );
+        }
+
+        self.atlases = [glyph_atlas.generation(), image_atlas.generation()];
+
+        let (previous, current) = (&self.previous, &self.instances);
+
+        self.regions.clear();
+        self.full_damage = false;
+        for index in 0..previous.len().max(current.len()) {
+            let (old, new) = (previous.get(index), current.get(index));
+
+            let changed = match (old, new) {
+                (Some(old), Some(new)) => bytemuck::bytes_of(old) != bytemuck::bytes_of(new),
+                _ => true,
+            };
+
+            if !changed {
+                continue;
+            }
+
+            for instance in [old, new].into_iter().flatten() {
+                match bounds(instance) {
+                    Some(extents) => self.regions.push(extents),
+                    None => self.full_damage = true,
+                }
+            }
+
+            if self.full_damage {
+                break;
+            }
+        }
+
+        instance_buffer.update(device, queue, previous, current);
+
+        self.full_damage || !self.regions.is_empty()
+    }
+
+    /// What the last `prepare` found to have changed
+    pub fn damage(&self) -> Damage<'_> {
+        match self.full_damage {
+            true => Damage::Full,
+            false => Damage::Regions(&self.regions),
+        }
+    }
+
+    /// Draws what the last `prepare` wrote to `instance_buffer`
+    pub fn render(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        vertex_buffer: &buffers::VertexBuffer,
+        index_buffer: &buffers::IndexBuffer,
+        instance_buffer: &buffers::InstanceBuffer,
+        glyph_atlas: &text::atlas::GlyphAtlas,
+        image_atlas: &rectangle::image::ImageAtlas,
+    ) {
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+
+        render_pass.set_bind_group(1, &glyph_atlas.bind_group, &[]);
+        render_pass.set_bind_group(2, &image_atlas.bind_group, &[]);
+
+        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        render_pass.draw_indexed(0..index_buffer.size(), 0, 0..instance_buffer.size());
+    }
+}
+
+// Area an instance covers, or `None` if it's rotated or skewed and working it out isn't worth it
+fn bounds(instance: &buffers::Instance) -> Option<rectangle::Extents> {
+    if instance.rotation != 0.0 || instance.skew != [0.0, 0.0] {
+        return None;
+    }
+
+    let [x, y, width, height] = instance.dimensions;
+    let [scale_x, scale_y] = instance.scale;
+    let [translate_x, translate_y] = instance.translate;
+
+    Some(rectangle::Extents {
+        x: (x + translate_x) * scale_x,
+        y: (y + translate_y) * scale_y,
+        width: width * scale_x,
+        height: height * scale_y,
+    })
+}
+
+impl Deref for Tree {
+    type Target = Node;
+    fn deref(&self) -> &Self::Target {
+        &self.node
+    }
+}
+
+impl DerefMut for Tree {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.dirty = true;
+        &mut self.node
+    }
+}
+
+/// Called with the node it was set on, as clicks are usually answered by changing the node
+pub type ClickHandler = Box<dyn FnMut(&mut Node, &input::Click)>;
+pub type ScrollHandler = Box<dyn FnMut(&mut Node, &input::Scroll)>;
+
+/// Restyles the rectangle while the node is in a `State`. Layout uses the unstyled rectangle, so
+/// a style shouldn't change the size.
+pub type Style = Box<dyn Fn(rectangle::Rectangle) -> rectangle::Rectangle>;
+
+pub struct Node {
+    pub children: Vec<Node>,
+    pub data: rectangle::Rectangle,
+    /// Drawn in the content box, which is sized to fit it
+    pub text: Option<text::Text>,
+    pub layout: Option<layout::Layout>,
+    pub flex_grow: f32,
+    pub flex_shrink: f32,
+    pub on_click: Option<ClickHandler>,
+    pub on_scroll: Option<ScrollHandler>,
+    /// Indexed by `State`
+    states: [bool; 3],
+    styles: [Option<Style>; 3],
+    /// Set on the focused node when the focus was moved with the keyboard
+    focus_ring: bool,
+    /// Border box size the node had before a layout resized it, and the size it was given
+    basis: (f32, f32),
+    resolved: Option<(f32, f32)>,
+}
+
+impl Node {
+    pub fn new(rectangle: rectangle::Rectangle) -> Self {
+        Self {
+            data: rectangle,
+            children: Vec::new(),
+            text: None,
+            layout: None,
+            flex_grow: 0.0,
+            flex_shrink: 1.0,
+            on_click: None,
+            on_scroll: None,
+            states: [false; 3],
+            styles: [None, None, None],
+            focus_ring: false,
+            basis: (0.0, 0.0),
+            resolved: None,
+        }
+    }
+
+    pub fn add_child(&mut self, child: impl Into<Node>) {
+        self.children.push(child.into());
+    }
+
+    pub fn with_child(mut self, child: impl Into<Node>) -> Self {
+        self.add_child(child);
+        self
+    }
+
+    pub fn set_text(mut self, text: text::Text) -> Self {
+        let extents = text.get_extents();
+
+        let data = std::mem::take(&mut self.data);
+        self.data = data.set_size(extents.width, extents.height);
+        self.text = Some(text);
+        self
+    }
+
+    /// Lays children out automatically. Without a layout children keep their own coordinates
+    pub fn set_layout(mut self, layout: layout::Layout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    pub fn set_flex_grow(mut self, flex_grow: f32) -> Self {
+        self.flex_grow = flex_grow;
+        self
+    }
+
+    pub fn set_flex_shrink(mut self, flex_shrink: f32) -> Self {
+        self.flex_shrink = flex_shrink;
+        self
+    }
+
+    pub fn set_on_click(mut self, handler: impl FnMut(&mut Node, &input::Click) + 'static) -> Self {
+        self.on_click = Some(Box::new(handler));
+        self
+    }
+
+    pub fn set_on_scroll(
+        mut self,
+        handler: impl FnMut(&mut Node, &input::Scroll) + 'static,
+    ) -> Self {
+        self.on_scroll = Some(Box::new(handler));
+        self
+    }
+
+    /// Draws the node as `style` returns it while the node is in `state`, e.g.
+    /// `|rectangle| rectangle.set_background_color(1.0, 1.0, 1.0, 0.2)` for hover
+    pub fn set_style(
+        mut self,
+        state: State,
+        style: impl Fn(rectangle::Rectangle) -> rectangle::Rectangle + 'static,
+    ) -> Self {
+        self.styles[state as usize] = Some(Box::new(style));
+        self
+    }
+
+    pub fn is(&self, state: State) -> bool {
+        self.states[state as usize]
+    }
+
+    /// The rectangle with the styles of the states it's in applied, `None` if there are none
+    fn styled(&self) -> Option<rectangle::Rectangle> {
+        let mut styles = self
+            .styles
+            .iter()
+            .zip(self.states)
+            .filter_map(|(style, active)| style.as_ref().filter(|_| active))
+            .peekable();
+
+        if styles.peek().is_none() && !self.focus_ring {
+            return None;
+        }
+
+        let rectangle = styles.fold(self.data.clone(), |rectangle, style| style(rectangle));
+        Some(match self.focus_ring {
+            true => focus_ring(rectangle),
+            false => rectangle,
+        })
+    }
+
+    /// Calls `f` with the node and then its descendants, parents before their children
+    fn walk_mut(&mut self, f: &mut impl FnMut(&mut Node)) {
+        f(self);
+        self.children.iter_mut().for_each(|child| child.walk_mut(f));
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        let extents = self.data.get_extents();
+        (extents.x..extents.x + extents.width).contains(&x)
+            && (extents.y..extents.y + extents.height).contains(&y)
+    }
+
+    /// Puts the top-most node at `point` and its ancestors in `state` and takes every other node
+    /// out of it, `None` takes them all out. Returns whether this node is under `point`, and
+    /// whether the look of any node changed.
+    fn set_state_at(&mut self, state: State, point: Option<(f32, f32)>) -> (bool, bool) {
+        let mut hit = false;
+        let mut restyled = false;
+
+        // Only the top-most child is under the point, the ones below it are covered
+        for child in self.children.iter_mut().rev() {
+            let (child_hit, child_restyled) = child.set_state_at(state, point.filter(|_| !hit));
+            hit |= child_hit;
+            restyled |= child_restyled;
+        }
+
+        hit = hit || point.is_some_and(|(x, y)| self.contains(x, y));
+
+        let previous = std::mem::replace(&mut self.states[state as usize], hit);
+        restyled |= previous != hit && self.styles[state as usize].is_some();
+
+        (hit, restyled)
+    }
+
+    /// Border box size layout starts from. That's the size that was set, not the one the last
+    /// layout resolved, unless the size was changed since.
+    fn basis(&self) -> (f32, f32) {
+        let extents = self.data.get_extents();
+        let size = (extents.width, extents.height);
+
+        match self.resolved {
+            Some(resolved) if resolved == size => self.basis,
+            _ => size,
+        }
+    }
+
+    /// Gives the node its place in the layout, remembering the basis it was resolved from
+    fn set_layout_extents(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.basis = self.basis();
+
+        let data = std::mem::take(&mut self.data);
+        self.data = data.set_coordinates(x, y).set_extents_size(width, height);
+
+        let extents = self.data.get_extents();
+        self.resolved = Some((extents.width, extents.height));
+    }
+
+    /// Puts the top-most node at `x`, `y` and its ancestors in `state`, or takes them out of it,
+    /// leaving every other node as it is. Returns whether this node is under the point, and
+    /// whether the look of any node changed.
+    fn set_state_under(&mut self, state: State, x: f32, y: f32, value: bool) -> (bool, bool) {
+        let mut hit = false;
+        let mut restyled = false;
+
+        // Only the top-most child is under the point, the ones below it are covered
+        for child in self.children.iter_mut().rev() {
+            let (child_hit, child_restyled) = child.set_state_under(state, x, y, value);
+            restyled |= child_restyled;
+            if child_hit {
+                hit = true;
+                break;
+            }
+        }
+
+        if !hit && !self.contains(x, y) {
+            return (false, restyled);
+        }
+
+        let previous = std::mem::replace(&mut self.states[state as usize], value);
+        restyled |= previous != value && self.styles[state as usize].is_some();
+
+        (true, restyled)
+    }
+
+    /// Positions the whole subtree, parents first as children are placed in their content box
+    pub fn layout(&mut self) {
+        if let Some(layout) = self.layout {
+            layout.arrange(&self.data.get_content_extents(), &mut self.children);
+        }
+
+        self.children.iter_mut().for_each(|child| child.layout());
+    }
+
+    /// Hit tests the subtree at `x`, `y` in surface coordinates, ignoring transforms, and offers
+    /// the event to the top-most node there and then its ancestors until `handle` takes it.
+    /// `None` if no node in it is under the point, otherwise whether the event was taken.
+    fn route(
+        &mut self,
+        x: f32,
+        y: f32,
+        handle: &mut impl FnMut(&mut Node) -> bool,
+    ) -> Option<bool> {
+        // Children are drawn over their parent and later siblings over earlier ones
+        let hit = match self
+            .children
+            .iter_mut()
+            .rev()
+            .find_map(|child| child.route(x, y, handle))
+        {
+            Some(true) => return Some(true),
+            Some(false) => true,
+            None => self.contains(x, y),
+        };
+
+        hit.then(|| handle(self))
+    }
+
+    /// Runs the click handler, if there is one, with the click relative to the node
+    fn click(&mut self, click: &input::Click) -> bool {
+        // Taken out for the call, so the handler can have the node
+        let Some(mut handler) = self.on_click.take() else {
+            return false;
+        };
+
+        let extents = self.data.get_extents();
+        handler(
+            self,
+            &input::Click {
+                x: click.x - extents.x,
+                y: click.y - extents.y,
+                ..*click
+            },
+        );
+
+        // Unless the handler set a new one
+        self.on_click.get_or_insert(handler);
+        self.states[State::Focus as usize] = true;
+
+        true
+    }
+
+    /// Runs the scroll handler, if there is one, with the scroll relative to the node
+    fn scroll(&mut self, scroll: &input::Scroll) -> bool {
+        let Some(mut handler) = self.on_scroll.take() else {
+            return false;
+        };
+
+        let extents = self.data.get_extents();
+        handler(
+            self,
+            &input::Scroll {
+                x: scroll.x - extents.x,
+                y: scroll.y - extents.y,
+                ..*scroll
+            },
+        );
+
+        self.on_scroll.get_or_insert(handler);
+
+        true
+    }
+
+    fn collect_instances(
+        &self,
+        instances: &mut Vec<buffers::Instance>,
+        glyph_atlas: &mut text::atlas::GlyphAtlas,
+        image_atlas: &mut rectangle::image::ImageAtlas,
+        queue: &wgpu::Queue,
+        scale: f32,
+    ) {
+        let styled = self.styled();
+        let rectangle = styled.as_ref().unwrap_or(&self.data);
+        let mut instance = rectangle.get_instance();
+
+        // Without its image the rectangle is still drawn, just with the background color
+        if let Some(image) = rectangle.get_background_image() {
+            let tile = [instance.image[2] * scale, instance.image[3] * scale];
+            match image_atlas.get(queue, image, tile) {
+                Some(uv) => instance.texture = uv,
+                None => instance.flags[0] = buffers::InstanceKind::Rectangle as u32,
+            }
+        }
+
+        instances.push(instance);
+
+        if let Some(text) = self.text.as_ref() {
+            let content = self.data.get_content_extents();
+            text.collect_instances(
+                (content.x, content.y),
+                &instance,
+                glyph_atlas,
+                queue,
+                scale,
+                instances,
+            );
+        }
+
+        self.children.iter().for_each(|child| {
+            child.collect_instances(instances, glyph_atlas, image_atlas, queue, scale)
+        });
+    }
+}
+
+/// Shown around the node focused with the keyboard, in place of its own outline
+fn focus_ring(rectangle: rectangle::Rectangle) -> rectangle::Rectangle {
+    rectangle
+        .set_outline_style(rectangle::outline::OutlineStyle::Solid)
+        .set_outline_width(2.0)
+        .set_outline_offset(2.0)
+        .set_outline_color(0.2, 0.6, 1.0, 1.0)
+}
+
+impl From<rectangle::Rectangle> for Node {
+    fn from(rectangle: rectangle::Rectangle) -> Self {
+        Node::new(rectangle)
+    }
+}
+
+impl From<text::Text> for Node {
+    fn from(text: text::Text) -> Self {
+        Node::new(rectangle::Rectangle::default()).set_text(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rectangle::Rectangle;
+
+    fn node(width: f32, height: f32) -> Node {
+        Node::new(Rectangle::default().set_size(width, height))
+    }
+
+    fn extents(node: &Node) -> (f32, f32, f32, f32) {
+        let extents = node.data.get_extents();
+        (extents.x, extents.y, extents.width, extents.height)
+    }
+
+    #[test]
+    fn center_section_is_centered_on_the_bar() {
+        let mut tree = Tree::new(Rectangle::default().set_size(200.0, 20.0));
+        tree.section_mut(Section::Start).add_child(node(60.0, 10.0));
+        tree.section_mut(Section::Start).add_child(node(20.0, 10.0));
+        tree.section_mut(Section::Center)
+            .add_child(node(40.0, 10.0));
+        tree.section_mut(Section::End).add_child(node(10.0, 10.0));
+        tree.layout();
+
+        // Centered on the whole bar, not between the start and end sections
+        let [start, center, end] = [0, 1, 2].map(|index| extents(&tree.children[index]));
+        assert_eq!(start, (0.0, 0.0, 80.0, 20.0));
+        assert_eq!(center, (80.0, 0.0, 40.0, 20.0));
+        assert_eq!(end, (190.0, 0.0, 10.0, 20.0));
+
+        // Children are centered across the bar
+        assert_eq!(
+            extents(&tree.children[1].children[0]),
+            (80.0, 5.0, 40.0, 10.0)
+        );
+    }
+
+    #[test]
+    fn sections_follow_the_direction() {
+        let mut tree = Tree::new(Rectangle::default().set_size(20.0, 200.0));
+        tree.set_direction(layout::Direction::Column);
+        tree.section_mut(Section::Start).add_child(node(10.0, 70.0));
+        tree.section_mut(Section::Center)
+            .add_child(node(10.0, 30.0));
+        tree.layout();
+
+        assert_eq!(extents(&tree.children[0]), (0.0, 0.0, 20.0, 70.0));
+        assert_eq!(extents(&tree.children[1]), (0.0, 85.0, 20.0, 30.0));
+        assert_eq!(extents(&tree.children[2]), (0.0, 200.0, 20.0, 0.0));
+    }
+
+    #[test]
+    fn repeated_layout_keeps_sections_in_place() {
+        let mut tree = Tree::new(Rectangle::default().set_size(200.0, 20.0));
+        tree.section_mut(Section::Start).add_child(node(60.0, 10.0));
+        tree.section_mut(Section::Center)
+            .add_child(node(40.0, 10.0));
+        tree.layout();
+        tree.layout();
+
+        assert_eq!(extents(&tree.children[1]), (80.0, 0.0, 40.0, 20.0));
+        assert_eq!(
+            extents(&tree.children[1].children[0]),
+            (80.0, 5.0, 40.0, 10.0)
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use super::Node;
+use crate::rectangle::Extents;
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum Direction {
+    #[default]
+    Row,
+    Column,
+}
+
+impl Direction {
+    /// Maps between (x, y) and (main, cross), works in both directions as it's a swap
+    pub fn axes(self, x: f32, y: f32) -> (f32, f32) {
+        match self {
+            Direction::Row => (x, y),
+            Direction::Column => (y, x),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum JustifyContent {
+    #[default]
+    Start,
+    End,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum AlignItems {
+    #[default]
+    Start,
+    End,
+    Center,
+    Stretch,
+}
+
+/// Flexbox-like layout of a node's children along a main axis
+#[derive(Default, Clone, Copy)]
+pub struct Layout {
+    pub direction: Direction,
+    pub justify_content: JustifyContent,
+    pub align_items: AlignItems,
+    pub gap: f32,
+    pub wrap: bool,
+}
+
+impl Layout {
+    pub fn set_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn set_justify_content(mut self, justify_content: JustifyContent) -> Self {
+        self.justify_content = justify_content;
+        self
+    }
+
+    pub fn set_align_items(mut self, align_items: AlignItems) -> Self {
+        self.align_items = align_items;
+        self
+    }
+
+    pub fn set_gap(mut self, gap: f32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn set_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    fn axes(&self, x: f32, y: f32) -> (f32, f32) {
+        self.direction.axes(x, y)
+    }
+
+    /// Main axis size needed to fit `children` on a single line
+    pub(super) fn natural_main(&self, children: &[Node]) -> f32 {
+        let gaps = self.gap * children.len().saturating_sub(1) as f32;
+
+        children
+            .iter()
+            .map(|child| {
+                let (width, height) = child.basis();
+                self.axes(width, height).0
+            })
+            .sum::<f32>()
+            + gaps
+    }
+
+    /// Positions and sizes `children` inside `content`, the parent's content box
+    pub(super) fn arrange(&self, content: &Extents, children: &mut [Node]) {
+        let (origin_main, origin_cross) = self.axes(content.x, content.y);
+        let (available_main, available_cross) = self.axes(content.width, content.height);
+
+        let sizes: Vec<(f32, f32)> = children
+            .iter()
+            .map(|child| {
+                let (width, height) = child.basis();
+                self.axes(width, height)
+            })
+            .collect();
+
+        let lines = self.lines(&sizes, available_main);
+        let single_line = lines.len() == 1;
+
+        let mut cross_offset = origin_cross;
+        for line in lines {
+            let mut mains: Vec<f32> = sizes[line.clone()].iter().map(|(main, _)| *main).collect();
+            let gaps = self.gap * (mains.len().saturating_sub(1)) as f32;
+            let free = available_main - mains.iter().sum::<f32>() - gaps;
+
+            resolve_flexible(&mut mains, &children[line.clone()], free);
+
+            // A non-wrapping container has a single line spanning its whole cross size
+            let line_cross = if single_line && !self.wrap {
+                available_cross
+            } else {
+                sizes[line.clone()]
+                    .iter()
+                    .map(|(_, cross)| *cross)
+                    .fold(0.0, f32::max)
+            };
+
+            let remaining = (available_main - mains.iter().sum::<f32>() - gaps).max(0.0);
+            let count = mains.len() as f32;
+            let (leading, between) = match self.justify_content {
+                JustifyContent::Start => (0.0, 0.0),
+                JustifyContent::End => (remaining, 0.0),
+                JustifyContent::Center => (remaining / 2.0, 0.0),
+                JustifyContent::SpaceBetween if count > 1.0 => (0.0, remaining / (count - 1.0)),
+                JustifyContent::SpaceBetween => (0.0, 0.0),
+                JustifyContent::SpaceAround => (remaining / count / 2.0, remaining / count),
+                JustifyContent::SpaceEvenly => {
+                    (remaining / (count + 1.0), remaining / (count + 1.0))
+                }
+            };
+
+            let mut main_offset = origin_main + leading;
+            for ((child, main), (_, cross)) in children[line.clone()]
+                .iter_mut()
+                .zip(mains)
+                .zip(&sizes[line])
+            {
+                let cross = match self.align_items {
+                    AlignItems::Stretch => line_cross,
+                    _ => *cross,
+                };
+                let cross_position = cross_offset
+                    + match self.align_items {
+                        AlignItems::Start | AlignItems::Stretch => 0.0,
+                        AlignItems::End => line_cross - cross,
+                        AlignItems::Center => (line_cross - cross) / 2.0,
+                    };
+
+                let (x, y) = self.axes(main_offset, cross_position);
+                let (width, height) = self.axes(main, cross);
+
+                child.set_layout_extents(x, y, width, height);
+
+                main_offset += main + self.gap + between;
+            }
+
+            cross_offset += line_cross + self.gap;
+        }
+    }
+
+    /// Splits children into lines, each holding at least one child
+    fn lines(&self, sizes: &[(f32, f32)], available_main: f32) -> Vec<std::ops::Range<usize>> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut used = 0.0;
+
+        for (index, (main, _)) in sizes.iter().enumerate() {
+            let needed = if index == start {
+                *main
+            } else {
+                used + self.gap + main
+            };
+
+            if self.wrap && index != start && needed > available_main {
+                lines.push(start..index);
+                start = index;
+                used = *main;
+            } else {
+                used = needed;
+            }
+        }
+
+        lines.push(start..sizes.len());
+        lines
+    }
+}
+
+/// Grows or shrinks `mains` to absorb `free` space according to each child's flex factors
+fn resolve_flexible(mains: &mut [f32], children: &[Node], free: f32) {
+    if free > 0.0 {
+        let total: f32 = children.iter().map(|child| child.flex_grow).sum();
+        if total <= 0.0 {
+            return;
+        }
+
+        mains
+            .iter_mut()
+            .zip(children)
+            .for_each(|(main, child)| *main += free * child.flex_grow / total);
+    } else if free < 0.0 {
+        // Like CSS, shrinking is weighted by the base size so small items don't vanish first
+        let total: f32 = mains
+            .iter()
+            .zip(children)
+            .map(|(main, child)| main * child.flex_shrink)
+            .sum();
+        if total <= 0.0 {
+            return;
+        }
+
+        mains.iter_mut().zip(children).for_each(|(main, child)| {
+            *main = (*main + free * *main * child.flex_shrink / total).max(0.0)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rectangle::Rectangle;
+
+    fn node(width: f32, height: f32) -> Node {
+        Node::new(Rectangle::default().set_size(width, height))
+    }
+
+    fn container(layout: Layout, width: f32, height: f32, children: Vec<Node>) -> Node {
+        let mut container = node(width, height).set_layout(layout);
+        container.children = children;
+        container
+    }
+
+    fn sizes(container: &Node) -> Vec<(f32, f32)> {
+        container
+            .children
+            .iter()
+            .map(|child| {
+                let extents = child.data.get_extents();
+                (extents.width, extents.height)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grow() {
+        let mut row = container(
+            Layout::default(),
+            100.0,
+            10.0,
+            vec![
+                node(10.0, 10.0).set_flex_grow(1.0),
+                node(10.0, 10.0).set_flex_grow(3.0),
+            ],
+        );
+        row.layout();
+
+        assert_eq!(sizes(&row), [(30.0, 10.0), (70.0, 10.0)]);
+        assert_eq!(row.children[1].data.get_extents().x, 30.0);
+    }
+
+    #[test]
+    fn shrink() {
+        let mut row = container(
+            Layout::default(),
+            60.0,
+            10.0,
+            vec![node(60.0, 10.0), node(30.0, 10.0).set_flex_shrink(0.0)],
+        );
+        row.layout();
+
+        assert_eq!(sizes(&row), [(30.0, 10.0), (30.0, 10.0)]);
+    }
+
+    #[test]
+    fn wrap() {
+        let layout = Layout::default()
+            .set_wrap(true)
+            .set_gap(5.0)
+            .set_align_items(AlignItems::Stretch);
+        let mut row = container(
+            layout,
+            50.0,
+            100.0,
+            vec![node(30.0, 10.0), node(30.0, 20.0), node(10.0, 5.0)],
+        );
+        row.layout();
+
+        assert_eq!(sizes(&row), [(30.0, 10.0), (30.0, 20.0), (10.0, 20.0)]);
+        let positions: Vec<_> = row
+            .children
+            .iter()
+            .map(|child| (child.data.get_extents().x, child.data.get_extents().y))
+            .collect();
+        assert_eq!(positions, [(0.0, 0.0), (0.0, 15.0), (35.0, 15.0)]);
+    }
+
+    #[test]
+    fn repeated_layout() {
+        let mut row = container(
+            Layout::default(),
+            100.0,
+            10.0,
+            vec![node(40.0, 10.0).set_flex_grow(1.0), node(40.0, 10.0)],
+        );
+        row.layout();
+
+        // Growing and then shrinking again starts from the sizes that were set each time
+        row.data = Rectangle::default().set_size(50.0, 10.0);
+        row.layout();
+        assert_eq!(sizes(&row), [(25.0, 10.0), (25.0, 10.0)]);
+
+        row.data = Rectangle::default().set_size(100.0, 10.0);
+        row.layout();
+        row.layout();
+        assert_eq!(sizes(&row), [(60.0, 10.0), (40.0, 10.0)]);
+
+        // Stretched lines shrink back once they no longer wrap
+        let layout = Layout::default()
+            .set_wrap(true)
+            .set_align_items(AlignItems::Stretch);
+        let mut wrapped = container(
+            layout,
+            40.0,
+            100.0,
+            vec![node(30.0, 10.0), node(30.0, 20.0)],
+        );
+        wrapped.layout();
+        wrapped.data = Rectangle::default().set_size(20.0, 100.0);
+        wrapped.layout();
+        assert_eq!(sizes(&wrapped), [(20.0, 10.0), (20.0, 20.0)]);
+    }
+
+    #[test]
+    fn size_set_after_layout_is_the_new_basis() {
+        let mut row = container(
+            Layout::default(),
+            100.0,
+            10.0,
+            vec![node(10.0, 10.0).set_flex_grow(1.0), node(10.0, 10.0)],
+        );
+        row.layout();
+
+        row.children[1].data = Rectangle::default().set_size(50.0, 10.0);
+        row.layout();
+        assert_eq!(sizes(&row), [(50.0, 10.0), (50.0, 10.0)]);
+    }
+}
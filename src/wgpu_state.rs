@@ -1,26 +1,115 @@
-use raw_window_handle::{RawDisplayHandle, WaylandDisplayHandle};
+use crate::config;
+use raw_window_handle::{RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle};
+use status_bar::renderer::{self, Renderer};
 use std::ptr::NonNull;
 use wayland_client::Connection;
 
+/// Everything on the GPU that doesn't belong to a single output, shared by all of them
 pub struct WgpuState {
     pub instance: wgpu::Instance,
     pub raw_display_handle: RawDisplayHandle,
+    pub adapter: wgpu::Adapter,
+    pub renderer: Renderer,
+    /// Kept to pick another adapter when this one can't present to a surface
+    order: Vec<wgpu::Backends>,
+    power_preference: wgpu::PowerPreference,
+    /// Set when `reselect` replaced the device, surfaces made before that have to be recreated
+    pub reselected: bool,
 }
 
 impl WgpuState {
-    pub fn new(conn: &Connection) -> Self {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backend::Vulkan.into(),
-            ..Default::default()
-        });
-
+    pub fn new(conn: &Connection, config: &config::Config) -> Self {
         let raw_display_handle = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(
             NonNull::new(conn.backend().display_ptr() as *mut _).unwrap(),
         ));
 
+        Self::with_display(raw_display_handle, config)
+    }
+
+    /// Starts over with a new adapter and device, for when the device was lost. Every surface has
+    /// to be recreated afterwards.
+    pub fn recreate(&mut self, config: &config::Config) {
+        *self = Self::with_display(self.raw_display_handle, config);
+    }
+
+    /// Picks the backend and power preference from the environment if set there, otherwise from
+    /// `config`
+    fn with_display(raw_display_handle: RawDisplayHandle, config: &config::Config) -> Self {
+        let order = match wgpu::util::backend_bits_from_env() {
+            Some(backends) => vec![backends],
+            None => config.backend.order().to_vec(),
+        };
+        let power_preference =
+            wgpu::util::power_preference_from_env().unwrap_or(config.power_preference.into());
+
+        // No surface exists yet to check compatibility against, `WgpuSurface::new` checks it and
+        // picks another adapter if need be
+        Self::select(raw_display_handle, order, power_preference, None)
+    }
+
+    /// Picks another adapter that can present to `window`, for when the one in use can't. Its
+    /// device replaces the old one, so every surface has to be recreated afterwards.
+    pub fn reselect(&mut self, window: RawWindowHandle) {
+        let order = std::mem::take(&mut self.order);
+        *self = Self::select(
+            self.raw_display_handle,
+            order,
+            self.power_preference,
+            Some(window),
+        );
+        self.reselected = true;
+    }
+
+    /// Creates a surface for `window`, `None` if the instance's backends can't
+    pub fn create_surface(
+        instance: &wgpu::Instance,
+        raw_display_handle: RawDisplayHandle,
+        window: RawWindowHandle,
+    ) -> Option<wgpu::Surface<'static>> {
+        unsafe {
+            instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
+                raw_display_handle,
+                raw_window_handle: window,
+            })
+        }
+        .inspect_err(|err| log::warn!("failed to create surface: {err}"))
+        .ok()
+    }
+
+    fn select(
+        raw_display_handle: RawDisplayHandle,
+        order: Vec<wgpu::Backends>,
+        power_preference: wgpu::PowerPreference,
+        window: Option<RawWindowHandle>,
+    ) -> Self {
+        let options = wgpu::RequestAdapterOptions {
+            power_preference,
+            ..Default::default()
+        };
+
+        let surface = window.map(|window| {
+            move |instance: &wgpu::Instance| {
+                Self::create_surface(instance, raw_display_handle, window)
+            }
+        });
+
+        let (instance, adapter) = renderer::request_adapter(
+            &order,
+            &options,
+            surface.as_ref().map(|surface| surface as _),
+        )
+        .expect("Failed to find suitable adapter");
+
+        let (device, queue) = renderer::request_device(&adapter).expect("Failed to request device");
+
         Self {
             instance,
             raw_display_handle,
+            adapter,
+            renderer: Renderer::new(device, queue),
+            order,
+            power_preference,
+            reselected: false,
         }
     }
 }
@@ -0,0 +1,234 @@
+//! Renders a catalogue of rectangles on a software adapter and compares them with the reference
+//! images in `tests/golden`. Run with `UPDATE_GOLDEN=1` to write the references instead, after
+//! checking the new images look right. Mismatching images are written to `target/golden`.
+//! Without a software adapter the test fails, unless `SKIP_GOLDEN=1` is set.
+
+use status_bar::{
+    headless::{Error, Headless},
+    rectangle::{border::BorderStyle, outline::OutlineStyle, Rectangle},
+    tree::Tree,
+};
+use std::path::{Path, PathBuf};
+
+const SIZE: u32 = 128;
+
+/// How far a channel may be off before the pixel counts as different, software rasterizers
+/// don't all round the same way
+const CHANNEL_TOLERANCE: u8 = 8;
+
+/// Fraction of pixels that may differ, for antialiased edges landing a little differently
+const PIXEL_TOLERANCE: f32 = 0.005;
+
+fn rectangle() -> Rectangle {
+    Rectangle::default()
+        .set_coordinates(32.0, 32.0)
+        .set_size(64.0, 64.0)
+        .set_background_color(0.2, 0.4, 0.8, 1.0)
+}
+
+fn bordered() -> Rectangle {
+    rectangle()
+        .set_border_size(6.0, 6.0, 6.0, 6.0)
+        .set_border_color(1.0, 0.8, 0.2, 1.0)
+}
+
+fn outlined() -> Rectangle {
+    rectangle()
+        .set_outline_width(4.0)
+        .set_outline_color(0.9, 0.1, 0.1, 1.0)
+}
+
+fn catalogue() -> Vec<(String, Rectangle)> {
+    let border_styles = [
+        ("none", BorderStyle::None),
+        ("solid", BorderStyle::Solid),
+        ("dotted", BorderStyle::Dotted),
+        ("dashed", BorderStyle::Dashed),
+        ("double", BorderStyle::Double),
+        ("groove", BorderStyle::Groove),
+        ("ridge", BorderStyle::Ridge),
+        ("inset", BorderStyle::Inset),
+        ("outset", BorderStyle::Outset),
+        ("hidden", BorderStyle::Hidden),
+    ];
+
+    let outline_styles = [
+        ("none", OutlineStyle::None),
+        ("solid", OutlineStyle::Solid),
+        ("dotted", OutlineStyle::Dotted),
+        ("dashed", OutlineStyle::Dashed),
+        ("double", OutlineStyle::Double),
+        ("groove", OutlineStyle::Groove),
+        ("ridge", OutlineStyle::Ridge),
+        ("hidden", OutlineStyle::Hidden),
+    ];
+
+    let catalogue = [
+        ("plain", rectangle()),
+        (
+            "border_size",
+            bordered().set_border_size(2.0, 6.0, 10.0, 14.0),
+        ),
+        (
+            "border_color",
+            bordered().set_border_color(0.1, 0.9, 0.3, 0.5),
+        ),
+        (
+            "border_colors",
+            bordered().set_border_colors(
+                [1.0, 0.0, 0.0, 1.0],
+                [0.0, 1.0, 0.0, 1.0],
+                [0.0, 0.0, 1.0, 1.0],
+                [1.0, 1.0, 1.0, 1.0],
+            ),
+        ),
+        (
+            "border_radius",
+            bordered().set_border_radius(0.0, 8.0, 16.0, 32.0),
+        ),
+        (
+            "border_radius_clamped",
+            bordered().set_border_radius(64.0, 64.0, 64.0, 64.0),
+        ),
+        ("outline_width", outlined().set_outline_width(8.0)),
+        ("outline_offset", outlined().set_outline_offset(6.0)),
+        (
+            "outline_color",
+            outlined().set_outline_color(0.1, 0.9, 0.3, 0.5),
+        ),
+        (
+            "outline_rounded",
+            outlined()
+                .set_outline_offset(4.0)
+                .set_border_radius(4.0, 8.0, 16.0, 24.0),
+        ),
+        ("opacity", rectangle().set_opacity(0.5)),
+        // Blur, sepia and hue-rotate aren't drawn by the shader yet, their references would
+        // just be `plain` again
+        ("brightness", rectangle().set_brightness(0.3)),
+        ("contrast", rectangle().set_contrast(2.0)),
+        ("grayscale", rectangle().set_grayscale(1.0)),
+        ("invert", rectangle().set_invert(1.0)),
+        ("saturate", rectangle().set_saturate(0.2)),
+        ("scale", rectangle().set_scale(0.5, 1.5)),
+        ("skew", rectangle().set_skew(10.0, 0.0)),
+        ("rotate", rectangle().set_rotate(10.0)),
+        ("translate", rectangle().set_translate([-16.0, 8.0])),
+        (
+            "box_shadow",
+            rectangle()
+                .set_boxshadow_offset(4.0, 8.0)
+                .set_boxshadow_softness(8.0)
+                .set_boxshadow_color(0.0, 0.0, 0.0, 0.8),
+        ),
+        (
+            "box_shadow_inset",
+            rectangle()
+                .set_boxshadow_offset(4.0, 8.0)
+                .set_boxshadow_softness(8.0)
+                .set_boxshadow_color(0.0, 0.0, 0.0, 0.8)
+                .set_boxshadow_inset(true),
+        ),
+    ];
+
+    let mut catalogue: Vec<(String, Rectangle)> = catalogue
+        .into_iter()
+        .map(|(name, rectangle)| (name.to_string(), rectangle))
+        .collect();
+
+    catalogue.extend(border_styles.into_iter().map(|(name, style)| {
+        (
+            format!("border_style_{name}"),
+            bordered().set_border_style(style),
+        )
+    }));
+
+    catalogue.extend(outline_styles.into_iter().map(|(name, style)| {
+        (
+            format!("outline_style_{name}"),
+            outlined().set_outline_width(6.0).set_outline_style(style),
+        )
+    }));
+
+    catalogue
+}
+
+fn render(headless: &mut Headless, rectangle: Rectangle) -> image::RgbaImage {
+    let mut tree = Tree::new(Rectangle::default());
+    tree.add_child(rectangle);
+
+    headless.render(&mut tree, SIZE, SIZE, 1.0)
+}
+
+/// Fraction of pixels differing by more than the tolerance, or `None` if the sizes differ
+fn difference(actual: &image::RgbaImage, expected: &image::RgbaImage) -> Option<f32> {
+    if actual.dimensions() != expected.dimensions() {
+        return None;
+    }
+
+    let different = actual
+        .pixels()
+        .zip(expected.pixels())
+        .filter(|(actual, expected)| {
+            actual
+                .0
+                .iter()
+                .zip(expected.0)
+                .any(|(a, e)| a.abs_diff(e) > CHANNEL_TOLERANCE)
+        })
+        .count();
+
+    Some(different as f32 / actual.pixels().len() as f32)
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+#[test]
+fn rectangles_match_golden_images() {
+    // Skipping is opt in, so a missing adapter can't pass for matching images
+    let mut headless = match Headless::software() {
+        Ok(headless) => headless,
+        Err(Error::NoAdapter) if std::env::var_os("SKIP_GOLDEN").is_some() => {
+            eprintln!("no software adapter available, skipping");
+            return;
+        }
+        Err(Error::NoAdapter) => panic!("no software adapter available, set SKIP_GOLDEN=1 to skip"),
+        Err(err) => panic!("golden tests need a software adapter: {err}"),
+    };
+
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let failed_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden");
+
+    let mut failures = Vec::new();
+
+    for (name, rectangle) in catalogue() {
+        let actual = render(&mut headless, rectangle);
+        let path = golden_dir().join(format!("{name}.png"));
+
+        if update {
+            actual.save(&path).unwrap();
+            continue;
+        }
+
+        let Ok(expected) = image::open(&path).map(|image| image.to_rgba8()) else {
+            failures.push(format!("{name}: no reference, run with UPDATE_GOLDEN=1"));
+            continue;
+        };
+
+        match difference(&actual, &expected) {
+            Some(different) if different <= PIXEL_TOLERANCE => continue,
+            Some(different) => failures.push(format!(
+                "{name}: {:.2}% of pixels differ",
+                different * 100.0
+            )),
+            None => failures.push(format!("{name}: size differs")),
+        }
+
+        std::fs::create_dir_all(&failed_dir).unwrap();
+        actual.save(failed_dir.join(format!("{name}.png"))).unwrap();
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}